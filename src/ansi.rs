@@ -0,0 +1,690 @@
+//! ANSI / VT escape sequence parsing
+//!
+//! [`Parser`] is an incremental state machine in the style of the DEC VT
+//! parser. It is fed one byte at a time, so sequences split across `add`
+//! calls or across the ring wrap need no special handling, and reports each
+//! complete piece of the stream as an [`Event`] with its absolute index range.
+//!
+//! Only 7-bit sequences are recognised. 8-bit C1 introducers (0x80 - 0x9f) are
+//! treated as text because they collide with UTF-8 continuation bytes.
+//!
+//! `HistoryBuffer::get_plain_vec` uses the parser to return history with the
+//! escape sequences removed:
+//!
+//! ```rust
+//! use historybuffer::HistoryBuffer;
+//!
+//! let mut hb = HistoryBuffer::new(64);
+//! hb.add(b"\x1b[1;31merr");
+//! hb.add(b"or\x1b[0m: \x1b]0;title\x07oops\n");
+//! assert_eq!(hb.get_plain_vec(0, 100), b"error: oops\n");
+//! ```
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::{HistoryBuffer, OffsetMap};
+
+// Longest OSC/DCS payload kept. Longer payloads are still consumed, but truncated.
+const MAX_STRING_LEN: usize = 8192;
+
+// Longest CSI parameter string kept.
+const MAX_PARAMS_LEN: usize = 64;
+
+const ESC: u8 = 0x1b;
+const BEL: u8 = 0x07;
+const CAN: u8 = 0x18;
+const SUB: u8 = 0x1a;
+
+/// A complete piece of the byte stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// A byte of text. Multibyte UTF-8 characters arrive one byte at a time.
+    Print(u8),
+    /// A C0 control such as `\n`, `\r`, `\t`, backspace or BEL.
+    Control(u8),
+    /// ESC followed by optional intermediates and a final byte, e.g. `ESC 7`.
+    Esc {
+        intermediates: Vec<u8>,
+        final_byte: u8,
+    },
+    /// A control sequence, e.g. `ESC [ 1 ; 31 m`.
+    Csi(Csi),
+    /// An Operating System Command payload, e.g. `0;title` from `ESC ] 0;title BEL`.
+    Osc(Vec<u8>),
+    /// A Device Control String payload.
+    Dcs(Vec<u8>),
+}
+
+/// A control sequence: `ESC [`, parameters, intermediates and a final byte.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Csi {
+    /// Raw parameter bytes, including any private marker such as `?`.
+    pub params: Vec<u8>,
+    pub intermediates: Vec<u8>,
+    pub final_byte: u8,
+}
+
+impl Csi {
+    /// private
+    ///
+    /// Gets the private marker (`<`, `=`, `>` or `?`) that starts the parameters.
+    pub fn private(&self) -> Option<u8> {
+        match self.params.first() {
+            Some(&b) if (b'<'..=b'?').contains(&b) => Some(b),
+            _ => None,
+        }
+    }
+
+    /// params
+    ///
+    /// Gets the numeric parameters. Sub-parameters separated by `:` are
+    /// flattened, and empty parameters are returned as 0.
+    pub fn params(&self) -> Vec<u16> {
+        let raw = match self.private() {
+            Some(_) => &self.params[1..],
+            None => &self.params[..],
+        };
+        if raw.is_empty() {
+            return Vec::new();
+        }
+        raw.split(|&b| b == b';' || b == b':')
+            .map(|p| {
+                p.iter().filter(|b| b.is_ascii_digit()).fold(0u16, |n, b| {
+                    n.saturating_mul(10).saturating_add((b - b'0') as u16)
+                })
+            })
+            .collect()
+    }
+
+    /// param
+    ///
+    /// Gets parameter i, substituting default when it is missing or 0.
+    pub fn param(&self, i: usize, default: u16) -> u16 {
+        match self.params().get(i) {
+            Some(&0) | None => default,
+            Some(&n) => n,
+        }
+    }
+}
+
+/// A text color set by SGR.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Color {
+    #[default]
+    Default,
+    /// One of the 256 indexed colors. 0 - 7 are the standard colors, 8 - 15 the bright ones.
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+/// Text attributes set by SGR (`ESC [ ... m`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Attrs {
+    pub fg: Color,
+    pub bg: Color,
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub blink: bool,
+    pub inverse: bool,
+    pub hidden: bool,
+    pub strike: bool,
+}
+
+impl Attrs {
+    /// apply_sgr
+    ///
+    /// Updates the attributes from an SGR sequence. Both the `38;5;n` and the
+    /// `38:5:n` / `38:2::r:g:b` forms of extended colors are understood.
+    pub fn apply_sgr(&mut self, csi: &Csi) {
+        if csi.private().is_some() || csi.final_byte != b'm' {
+            return;
+        }
+        let groups: Vec<Vec<u16>> = csi
+            .params
+            .split(|&b| b == b';')
+            .map(|group| {
+                Csi {
+                    params: group.to_vec(),
+                    ..Default::default()
+                }
+                .params()
+            })
+            .collect();
+        if groups.iter().all(|group| group.is_empty()) {
+            *self = Attrs::default();
+            return;
+        }
+
+        let mut i = 0;
+        while i < groups.len() {
+            let group = &groups[i];
+            i += 1;
+            let code = group.first().copied().unwrap_or(0);
+            match code {
+                38 | 48 | 58 => {
+                    let color = if group.len() > 1 {
+                        // Colon form carries its arguments in the same group
+                        match group[1..] {
+                            [5, n, ..] => Some(Color::Indexed(n as u8)),
+                            [2, _, r, g, b, ..] | [2, r, g, b] => {
+                                Some(Color::Rgb(r as u8, g as u8, b as u8))
+                            }
+                            _ => None,
+                        }
+                    } else {
+                        let arg = |k: usize| groups.get(i + k).and_then(|g| g.first()).copied();
+                        match arg(0) {
+                            Some(5) => {
+                                let n = arg(1);
+                                i += 2;
+                                n.map(|n| Color::Indexed(n as u8))
+                            }
+                            Some(2) => {
+                                let rgb = (arg(1), arg(2), arg(3));
+                                i += 4;
+                                match rgb {
+                                    (Some(r), Some(g), Some(b)) => {
+                                        Some(Color::Rgb(r as u8, g as u8, b as u8))
+                                    }
+                                    _ => None,
+                                }
+                            }
+                            _ => None,
+                        }
+                    };
+                    match (code, color) {
+                        (38, Some(color)) => self.fg = color,
+                        (48, Some(color)) => self.bg = color,
+                        _ => {} // Underline color is not tracked
+                    }
+                }
+                _ => self.apply_code(code, group.get(1).copied()),
+            }
+        }
+    }
+
+    fn apply_code(&mut self, code: u16, sub: Option<u16>) {
+        match code {
+            0 => *self = Attrs::default(),
+            1 => self.bold = true,
+            2 => self.dim = true,
+            3 => self.italic = true,
+            4 => self.underline = sub != Some(0),
+            5 | 6 => self.blink = true,
+            7 => self.inverse = true,
+            8 => self.hidden = true,
+            9 => self.strike = true,
+            21 => self.underline = true,
+            22 => (self.bold, self.dim) = (false, false),
+            23 => self.italic = false,
+            24 => self.underline = false,
+            25 => self.blink = false,
+            27 => self.inverse = false,
+            28 => self.hidden = false,
+            29 => self.strike = false,
+            30..=37 => self.fg = Color::Indexed((code - 30) as u8),
+            39 => self.fg = Color::Default,
+            40..=47 => self.bg = Color::Indexed((code - 40) as u8),
+            49 => self.bg = Color::Default,
+            90..=97 => self.fg = Color::Indexed((code - 90 + 8) as u8),
+            100..=107 => self.bg = Color::Indexed((code - 100 + 8) as u8),
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum StringKind {
+    #[default]
+    Osc,
+    Dcs,
+    Ignored, // SOS, PM and APC
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum State {
+    #[default]
+    Ground,
+    Escape,
+    EscIntermediate,
+    Csi,
+    CsiIgnore,
+    String(StringKind),
+    StringEscape(StringKind),
+}
+
+/// Incremental escape sequence parser.
+#[derive(Debug, Clone, Default)]
+pub struct Parser {
+    state: State,
+    start: usize,
+    params: Vec<u8>,
+    intermediates: Vec<u8>,
+    string: Vec<u8>,
+}
+
+impl Parser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// in_sequence
+    ///
+    /// True while the parser is part way through an escape sequence.
+    pub fn in_sequence(&self) -> bool {
+        self.state != State::Ground
+    }
+
+    /// feed
+    ///
+    /// Feeds the byte at absolute index to the parser, returning an event and
+    /// the index range it covers once a piece of the stream is complete.
+    pub fn feed(&mut self, index: usize, byte: u8) -> Option<(Range<usize>, Event)> {
+        let event = self.advance(index, byte)?;
+        let start = match event {
+            Event::Print(_) | Event::Control(_) => index,
+            _ => self.start,
+        };
+        Some((start..index + 1, event))
+    }
+
+    fn advance(&mut self, index: usize, byte: u8) -> Option<Event> {
+        if (byte == CAN || byte == SUB) && self.state != State::Ground {
+            self.state = State::Ground;
+            return None;
+        }
+        match self.state {
+            State::Ground => match byte {
+                ESC => self.enter_escape(index),
+                0x00..=0x1f | 0x7f => Some(Event::Control(byte)),
+                _ => Some(Event::Print(byte)),
+            },
+            State::Escape | State::EscIntermediate => match byte {
+                ESC => self.enter_escape(index),
+                0x00..=0x1f => Some(Event::Control(byte)),
+                0x20..=0x2f => {
+                    self.intermediates.push(byte);
+                    self.state = State::EscIntermediate;
+                    None
+                }
+                b'[' if self.state == State::Escape => {
+                    self.params.clear();
+                    self.state = State::Csi;
+                    None
+                }
+                b']' if self.state == State::Escape => self.enter_string(StringKind::Osc),
+                b'P' if self.state == State::Escape => self.enter_string(StringKind::Dcs),
+                b'X' | b'^' | b'_' if self.state == State::Escape => {
+                    self.enter_string(StringKind::Ignored)
+                }
+                0x30..=0x7e => {
+                    self.state = State::Ground;
+                    Some(Event::Esc {
+                        intermediates: core::mem::take(&mut self.intermediates),
+                        final_byte: byte,
+                    })
+                }
+                _ => None, // DEL is ignored
+            },
+            State::Csi | State::CsiIgnore => match byte {
+                ESC => self.enter_escape(index),
+                0x00..=0x1f => Some(Event::Control(byte)),
+                0x30..=0x3f if self.intermediates.is_empty() => {
+                    if self.params.len() < MAX_PARAMS_LEN {
+                        self.params.push(byte);
+                    }
+                    None
+                }
+                0x30..=0x3f => {
+                    self.state = State::CsiIgnore; // Parameter after an intermediate
+                    None
+                }
+                0x20..=0x2f => {
+                    self.intermediates.push(byte);
+                    None
+                }
+                0x40..=0x7e => {
+                    let ignore = self.state == State::CsiIgnore;
+                    self.state = State::Ground;
+                    let csi = Csi {
+                        params: core::mem::take(&mut self.params),
+                        intermediates: core::mem::take(&mut self.intermediates),
+                        final_byte: byte,
+                    };
+                    (!ignore).then_some(Event::Csi(csi))
+                }
+                _ => None,
+            },
+            State::String(kind) => match byte {
+                ESC => {
+                    self.state = State::StringEscape(kind);
+                    None
+                }
+                BEL if kind == StringKind::Osc => self.finish_string(kind),
+                _ => {
+                    if kind != StringKind::Ignored && self.string.len() < MAX_STRING_LEN {
+                        self.string.push(byte);
+                    }
+                    None
+                }
+            },
+            State::StringEscape(kind) => {
+                if byte == b'\\' {
+                    self.finish_string(kind)
+                } else {
+                    // ESC without the ST backslash cancels the string and starts a new sequence
+                    self.state = State::Escape;
+                    self.advance(index, byte)
+                }
+            }
+        }
+    }
+
+    fn enter_escape(&mut self, index: usize) -> Option<Event> {
+        self.state = State::Escape;
+        self.start = index;
+        self.intermediates.clear();
+        None
+    }
+
+    fn enter_string(&mut self, kind: StringKind) -> Option<Event> {
+        self.state = State::String(kind);
+        self.string.clear();
+        None
+    }
+
+    fn finish_string(&mut self, kind: StringKind) -> Option<Event> {
+        self.state = State::Ground;
+        let payload = core::mem::take(&mut self.string);
+        match kind {
+            StringKind::Osc => Some(Event::Osc(payload)),
+            StringKind::Dcs => Some(Event::Dcs(payload)),
+            StringKind::Ignored => None,
+        }
+    }
+}
+
+// How far back sync_point looks for an ESC.
+const SYNC_LOOKBACK: usize = 64 * 1024;
+
+/// sync_point
+///
+/// Finds where to start parsing so that events from index on come out as
+/// they would when parsing from the oldest byte. ESC resets the parser
+/// whatever state it was in, so the last ESC before index is such a point.
+/// With no ESC in the SYNC_LOOKBACK bytes before it, index itself is taken
+/// to be outside any sequence.
+pub(crate) fn sync_point(hb: &HistoryBuffer, index: usize) -> usize {
+    let index = index.clamp(hb.get_index(), hb.next_running);
+    let oldest = index.saturating_sub(SYNC_LOOKBACK).max(hb.get_index());
+    (oldest..index)
+        .rev()
+        .find(|&i| hb.get(i) == Some(ESC))
+        .unwrap_or(index)
+}
+
+/// scan
+///
+/// Parses the history from start_index up to end_index, calling f with every
+/// event. Parsing starts at `sync_point(start_index)`, so sequences which
+/// began shortly before start_index are still recognised, and f may see
+/// events that end before it.
+pub(crate) fn scan(
+    hb: &HistoryBuffer,
+    start_index: usize,
+    end_index: usize,
+    mut f: impl FnMut(Range<usize>, Event),
+) {
+    let start = sync_point(hb, start_index);
+    let (data, inn) = hb.get_vec_and_index(start, end_index.saturating_sub(start));
+    let mut parser = Parser::new();
+    for (i, &byte) in data.iter().enumerate() {
+        if let Some((range, event)) = parser.feed(inn + i, byte) {
+            f(range, event);
+        }
+    }
+}
+
+/// Is this event kept in the plain text view?
+pub(crate) fn is_plain(event: &Event) -> bool {
+    match event {
+        Event::Print(_) => true,
+        Event::Control(b) => matches!(b, b'\t' | b'\n' | b'\r' | 0x08),
+        _ => false,
+    }
+}
+
+impl HistoryBuffer {
+    /// get_plain_vec
+    ///
+    /// Returns history like `get_vec`, with CSI, OSC, DCS and other escape
+    /// sequences removed. Tab, newline, carriage return and backspace are kept;
+    /// other control characters are dropped.
+    ///
+    /// Note: Parsing starts at the last escape before start_index, so a
+    /// sequence that started before the requested range is still removed.
+    /// If the oldest bytes are themselves the tail of an evicted sequence,
+    /// or a string sequence began more than 64 KiB before start_index, its
+    /// tail may show up as text.
+    pub fn get_plain_vec(&self, start_index: usize, max_len: usize) -> Vec<u8> {
+        let end = start_index.saturating_add(max_len);
+        let mut v = Vec::new();
+        scan(self, start_index, end, |range, event| {
+            if let Event::Print(b) | Event::Control(b) = event
+                && range.start >= start_index
+                && is_plain(&event)
+            {
+                v.push(b);
+            }
+        });
+        v
+    }
+}
+
+/// PlainView
+///
+/// An incrementally updated plain text copy of the history, with an
+/// [`OffsetMap`] between text positions and raw indices. Call `update` after
+/// adding to the history; only the new bytes are parsed.
+#[derive(Debug, Clone, Default)]
+pub struct PlainView {
+    parser: Parser,
+    scanned: usize,
+    text: VecDeque<u8>,
+    map: OffsetMap,
+}
+
+impl PlainView {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// update
+    ///
+    /// Parses bytes added since the last update and forgets text whose raw
+    /// bytes have been evicted.
+    pub fn update(&mut self, hb: &HistoryBuffer) {
+        if self.scanned < hb.get_index() {
+            // Missed bytes were evicted before we saw them
+            self.parser = Parser::new();
+            self.scanned = hb.get_index();
+        }
+        let (data, inn) = hb.get_vec_and_index(self.scanned, hb.next_running - self.scanned);
+        for (i, &byte) in data.iter().enumerate() {
+            if let Some((range, event)) = self.parser.feed(inn + i, byte)
+                && let Event::Print(b) | Event::Control(b) = event
+                && is_plain(&event)
+            {
+                self.map.push(range.start);
+                self.text.push_back(b);
+            }
+        }
+        self.scanned = hb.next_running;
+
+        self.map.evict_before(hb.get_index());
+        let evicted = self.text.len() - self.map.get_filtered_range().len();
+        self.text.drain(..evicted);
+    }
+
+    /// map
+    ///
+    /// Gets the offset map between text positions and raw indices.
+    pub fn map(&self) -> &OffsetMap {
+        &self.map
+    }
+
+    /// get_text_index
+    ///
+    /// Gets the text position of the oldest plain byte.
+    pub fn get_text_index(&self) -> usize {
+        self.map.get_filtered_range().start
+    }
+
+    /// get_text
+    ///
+    /// Returns plain text from a range of text positions, like `get_vec`.
+    pub fn get_text(&self, start: usize, max_len: usize) -> Vec<u8> {
+        let first = self.get_text_index();
+        let skip = start.saturating_sub(first);
+        let end = start.saturating_add(max_len).saturating_sub(first);
+        self.text
+            .range(skip.min(self.text.len())..end.min(self.text.len()))
+            .copied()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn parse(input: &[u8]) -> Vec<(Range<usize>, Event)> {
+        let mut parser = Parser::new();
+        input
+            .iter()
+            .enumerate()
+            .filter_map(|(i, &b)| parser.feed(i, b))
+            .collect()
+    }
+
+    #[test]
+    fn test_parser() {
+        assert_eq!(
+            parse(b"a\x1b[?25h\x1b]8;;http://x\x1b\\\x1b7\x1bPq#\x1b\\\r"),
+            vec![
+                (0..1, Event::Print(b'a')),
+                (
+                    1..7,
+                    Event::Csi(Csi {
+                        params: b"?25".to_vec(),
+                        intermediates: vec![],
+                        final_byte: b'h',
+                    })
+                ),
+                (7..22, Event::Osc(b"8;;http://x".to_vec())),
+                (
+                    22..24,
+                    Event::Esc {
+                        intermediates: vec![],
+                        final_byte: b'7',
+                    }
+                ),
+                (24..30, Event::Dcs(b"q#".to_vec())),
+                (30..31, Event::Control(b'\r')),
+            ]
+        );
+
+        println!("        params:");
+        let csi = Csi {
+            params: b"?1;;38:5:196".to_vec(),
+            intermediates: vec![],
+            final_byte: b'm',
+        };
+        assert_eq!(csi.private(), Some(b'?'));
+        assert_eq!(csi.params(), vec![1, 0, 38, 5, 196]);
+        assert_eq!(csi.param(1, 7), 7);
+
+        println!("        sgr:");
+        let mut attrs = Attrs::default();
+        attrs.apply_sgr(&Csi {
+            params: b"1;38;5;196;48:2::1:2:3;4".to_vec(),
+            intermediates: vec![],
+            final_byte: b'm',
+        });
+        assert_eq!(
+            attrs,
+            Attrs {
+                fg: Color::Indexed(196),
+                bg: Color::Rgb(1, 2, 3),
+                bold: true,
+                underline: true,
+                ..Default::default()
+            }
+        );
+        attrs.apply_sgr(&Csi {
+            final_byte: b'm',
+            ..Default::default()
+        });
+        assert_eq!(attrs, Attrs::default());
+
+        println!("        cancelled:");
+        assert_eq!(parse(b"\x1b[3\x18x"), vec![(4..5, Event::Print(b'x'))]);
+    }
+
+    #[test]
+    fn test_plain_vec() {
+        let mut hb = HistoryBuffer::new(16);
+        hb.add(b"\x1b[32mgreen\x1b[");
+        hb.add(b"0m ok\x1b]0;t\x07\n");
+
+        println!("        split across add and ring wrap:");
+        assert_eq!(hb.get_index(), 8);
+        assert_eq!(hb.get_plain_vec(0, 100), b"en ok\n");
+
+        println!("        range starting inside a sequence:");
+        hb.add(b"\x1b[1mA\x1b[0m");
+        assert_eq!(hb.get_plain_vec(0, 100), b"\nA");
+        assert_eq!(hb.get_plain_vec(26, 6), b"A");
+        assert_eq!(hb.get_plain_vec(30, 3), b"");
+
+        println!("        parsing starts at the last ESC before the range:");
+        let mut hb = HistoryBuffer::new(1 << 18);
+        hb.add(b"\x1b]0;");
+        hb.add(&[b'x'; 100]);
+        hb.add(b"\x07tail");
+        assert_eq!(sync_point(&hb, 50), 0);
+        assert_eq!(hb.get_plain_vec(50, 100), b"tail");
+        hb.add(&[b'y'; SYNC_LOOKBACK]);
+        let last = hb.get_last_index();
+        assert_eq!(sync_point(&hb, last), last);
+        assert_eq!(hb.get_plain_vec(last, 10), b"y");
+    }
+
+    #[test]
+    fn test_plain_view() {
+        let mut hb = HistoryBuffer::new(16);
+        let mut view = PlainView::new();
+        hb.add(b"ab\x1b[1");
+        view.update(&hb);
+        hb.add(b"mcd");
+        view.update(&hb);
+
+        assert_eq!(view.get_text(0, 100), b"abcd");
+        assert_eq!(view.map().to_raw(2), Some(6));
+        assert_eq!(view.map().to_filtered(3), Err(2));
+
+        println!("        positions stable across eviction:");
+        hb.add(b"\x1b[0mefghijkl");
+        view.update(&hb);
+        assert_eq!(hb.get_index(), 4);
+        assert_eq!(view.get_text_index(), 2);
+        assert_eq!(view.get_text(0, 5), b"cde");
+        assert_eq!(view.map().to_raw(4), Some(12));
+        assert_eq!(view.map().to_filtered(12), Ok(4));
+    }
+}
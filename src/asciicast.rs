@@ -0,0 +1,416 @@
+//! asciicast v2
+//!
+//! Export and import of asciinema recordings. A cast file is a JSON header
+//! line followed by one `[time, "o", data]` line per chunk of output:
+//!
+//! ```text
+//! {"version": 2, "width": 80, "height": 24}
+//! [0.000000, "o", "$ ls\r\n"]
+//! [0.250000, "o", "Cargo.toml  src\r\n"]
+//! ```
+//!
+//! Each record of a [`TimedHistory`] becomes one output event. Times are
+//! written relative to the first exported event. Input, marker and resize
+//! events are skipped on import.
+//!
+//! ```rust
+//! use std::time::Duration;
+//! use historybuffer::{AsciicastHeader, TimedHistory};
+//!
+//! let mut th = TimedHistory::new(64);
+//! th.add_at(Duration::from_millis(0), b"$ ls\r\n");
+//! th.add_at(Duration::from_millis(250), b"Cargo.toml  src\r\n");
+//!
+//! let cast = th.to_asciicast(&AsciicastHeader::new(80, 24), 0..100);
+//! let (header, copy) = TimedHistory::from_asciicast(&cast, 64).unwrap();
+//! assert_eq!(header.width, 80);
+//! assert_eq!(copy.events(0..100), th.events(0..100));
+//! ```
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::ops::Range;
+use core::time::Duration;
+
+use crate::utf8::Utf8Decoder;
+use crate::{ParseError, TimedHistory};
+
+/// The header line of a cast file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsciicastHeader {
+    pub width: u16,
+    pub height: u16,
+    pub title: Option<String>,
+}
+
+impl AsciicastHeader {
+    pub fn new(width: u16, height: u16) -> Self {
+        Self {
+            width,
+            height,
+            title: None,
+        }
+    }
+}
+
+impl TimedHistory {
+    /// to_asciicast
+    ///
+    /// Writes the records overlapping range as an asciicast v2 recording.
+    /// Characters split across records are written whole with the later one.
+    pub fn to_asciicast(&self, header: &AsciicastHeader, range: Range<usize>) -> String {
+        let mut out = format!(
+            r#"{{"version": 2, "width": {}, "height": {}"#,
+            header.width, header.height
+        );
+        if let Some(title) = &header.title {
+            out.push_str(r#", "title": "#);
+            write_json_string(&mut out, title);
+        }
+        out.push_str("}\n");
+
+        let events = self.events(range);
+        let first = events.first().map_or(Duration::ZERO, |&(time, _)| time);
+        let mut decoder = Utf8Decoder::new();
+        for (time, data) in events {
+            let mut text = String::new();
+            for byte in data {
+                decoder.feed(byte, |c| text.push(c));
+            }
+            if text.is_empty() {
+                continue;
+            }
+            let _ = write!(
+                out,
+                "[{:.6}, \"o\", ",
+                time.saturating_sub(first).as_secs_f64()
+            );
+            write_json_string(&mut out, &text);
+            out.push_str("]\n");
+        }
+        out
+    }
+
+    /// from_asciicast
+    ///
+    /// Reads an asciicast v2 recording into a new history of at least
+    /// min_buf_size bytes. Output beyond that evicts older output as usual.
+    pub fn from_asciicast(
+        cast: &str,
+        min_buf_size: usize,
+    ) -> Result<(AsciicastHeader, TimedHistory), ParseError> {
+        let mut lines = cast
+            .lines()
+            .enumerate()
+            .filter(|(_, l)| !l.trim().is_empty());
+        let error = |line: usize, message| ParseError {
+            line: line + 1,
+            message,
+        };
+
+        let (n, first) = lines.next().ok_or(error(0, "missing header"))?;
+        let Some(Json::Object(fields)) = Json::parse(first) else {
+            return Err(error(n, "header is not a JSON object"));
+        };
+        let field = |name: &str| fields.iter().find(|(k, _)| k == name).map(|(_, v)| v);
+        if field("version") != Some(&Json::Number(2.0)) {
+            return Err(error(n, "not an asciicast v2 recording"));
+        }
+        let size = |name| match field(name) {
+            Some(&Json::Number(v)) if (0.0..=u16::MAX as f64).contains(&v) => Some(v as u16),
+            _ => None,
+        };
+        let (Some(width), Some(height)) = (size("width"), size("height")) else {
+            return Err(error(n, "header has no terminal size"));
+        };
+        let title = match field("title") {
+            Some(Json::String(title)) => Some(title.clone()),
+            _ => None,
+        };
+
+        let mut th = TimedHistory::new(min_buf_size);
+        for (n, line) in lines {
+            let Some(Json::Array(event)) = Json::parse(line) else {
+                return Err(error(n, "event is not a JSON array"));
+            };
+            let [Json::Number(time), Json::String(code), Json::String(data)] = &event[..] else {
+                return Err(error(n, "event is not [time, code, data]"));
+            };
+            let time =
+                Duration::try_from_secs_f64(*time).map_err(|_| error(n, "invalid event time"))?;
+            if code == "o" {
+                th.add_at(time, data.as_bytes());
+            }
+        }
+        Ok((
+            AsciicastHeader {
+                width,
+                height,
+                title,
+            },
+            th,
+        ))
+    }
+}
+
+fn write_json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 || c == '\u{7f}' => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+// Just enough JSON to read cast files.
+// Headers and events nest about 3 deep; deeper input is rejected rather than
+// recursed into.
+const MAX_DEPTH: usize = 8;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn parse(text: &str) -> Option<Json> {
+        let mut reader = Reader {
+            s: text.as_bytes(),
+            pos: 0,
+        };
+        let value = reader.value(0)?;
+        reader.skip_ws();
+        (reader.pos == reader.s.len()).then_some(value)
+    }
+}
+
+struct Reader<'a> {
+    s: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn skip_ws(&mut self) {
+        while self
+            .s
+            .get(self.pos)
+            .is_some_and(|b| b.is_ascii_whitespace())
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        self.skip_ws();
+        let found = self.s.get(self.pos) == Some(&byte);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn literal(&mut self, word: &str, value: Json) -> Option<Json> {
+        let found = self.s[self.pos..].starts_with(word.as_bytes());
+        found.then(|| {
+            self.pos += word.len();
+            value
+        })
+    }
+
+    fn value(&mut self, depth: usize) -> Option<Json> {
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        self.skip_ws();
+        match *self.s.get(self.pos)? {
+            b'n' => self.literal("null", Json::Null),
+            b't' => self.literal("true", Json::Bool(true)),
+            b'f' => self.literal("false", Json::Bool(false)),
+            b'"' => self.string().map(Json::String),
+            b'[' => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                if !self.eat(b']') {
+                    loop {
+                        items.push(self.value(depth + 1)?);
+                        if self.eat(b']') {
+                            break;
+                        }
+                        self.eat(b',').then_some(())?;
+                    }
+                }
+                Some(Json::Array(items))
+            }
+            b'{' => {
+                self.pos += 1;
+                let mut fields = Vec::new();
+                if !self.eat(b'}') {
+                    loop {
+                        self.skip_ws();
+                        let key = self.string()?;
+                        self.eat(b':').then_some(())?;
+                        fields.push((key, self.value(depth + 1)?));
+                        if self.eat(b'}') {
+                            break;
+                        }
+                        self.eat(b',').then_some(())?;
+                    }
+                }
+                Some(Json::Object(fields))
+            }
+            _ => {
+                let start = self.pos;
+                while self
+                    .s
+                    .get(self.pos)
+                    .is_some_and(|b| b.is_ascii_digit() || b"+-.eE".contains(b))
+                {
+                    self.pos += 1;
+                }
+                let number = core::str::from_utf8(&self.s[start..self.pos]).ok()?;
+                number.parse().ok().map(Json::Number)
+            }
+        }
+    }
+
+    fn string(&mut self) -> Option<String> {
+        if self.s.get(self.pos) != Some(&b'"') {
+            return None;
+        }
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            let start = self.pos;
+            while !matches!(self.s.get(self.pos), Some(b'"' | b'\\') | None) {
+                self.pos += 1;
+            }
+            out.push_str(core::str::from_utf8(&self.s[start..self.pos]).ok()?);
+            match self.s.get(self.pos)? {
+                b'"' => {
+                    self.pos += 1;
+                    return Some(out);
+                }
+                _ => {
+                    let escape = *self.s.get(self.pos + 1)?;
+                    self.pos += 2;
+                    match escape {
+                        b'"' => out.push('"'),
+                        b'\\' => out.push('\\'),
+                        b'/' => out.push('/'),
+                        b'b' => out.push('\u{8}'),
+                        b'f' => out.push('\u{c}'),
+                        b'n' => out.push('\n'),
+                        b'r' => out.push('\r'),
+                        b't' => out.push('\t'),
+                        b'u' => {
+                            let mut code = self.hex4()?;
+                            if (0xd800..0xdc00).contains(&code)
+                                && self.s[self.pos..].starts_with(b"\\u")
+                            {
+                                self.pos += 2;
+                                let low = self.hex4()?;
+                                code = 0x10000
+                                    + ((code - 0xd800) << 10)
+                                    + (low.wrapping_sub(0xdc00) & 0x3ff);
+                            }
+                            out.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                        }
+                        _ => return None,
+                    }
+                }
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Option<u32> {
+        let digits = core::str::from_utf8(self.s.get(self.pos..self.pos + 4)?).ok()?;
+        self.pos += 4;
+        u32::from_str_radix(digits, 16).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_asciicast() {
+        let mut th = TimedHistory::new(64);
+        th.add_at(Duration::from_millis(1000), b"\x1b[1m\"hi\"\\\r\n");
+        th.add_at(Duration::from_millis(1500), &"é".as_bytes()[..1]);
+        th.add_at(Duration::from_millis(2000), &"é!".as_bytes()[1..]);
+
+        let mut header = AsciicastHeader::new(100, 30);
+        header.title = Some("demo".to_string());
+        let cast = th.to_asciicast(&header, 0..100);
+        assert_eq!(
+            cast,
+            concat!(
+                "{\"version\": 2, \"width\": 100, \"height\": 30, \"title\": \"demo\"}\n",
+                "[0.000000, \"o\", \"\\u001b[1m\\\"hi\\\"\\\\\\r\\n\"]\n",
+                "[1.000000, \"o\", \"é!\"]\n",
+            )
+        );
+
+        println!("        import:");
+        let cast = concat!(
+            "{\"version\": 2, \"width\": 80, \"height\": 24, \"env\": {\"TERM\": \"xterm\"}}\n",
+            "[0.5, \"o\", \"a\\u00e9\\ud83d\\ude00\"]\n",
+            "[0.75, \"i\", \"q\"]\n",
+            "[1.25, \"o\", \"\\n\"]\n",
+        );
+        let (header, th) = TimedHistory::from_asciicast(cast, 64).unwrap();
+        assert_eq!(header, AsciicastHeader::new(80, 24));
+        assert_eq!(th.history().get_vec(0, 100), "aé😀\n".as_bytes());
+        assert_eq!(th.get_time(1), Some(Duration::from_millis(1250)));
+
+        let bad = "{\"version\": 2, \"width\": 80, \"height\": 24}\n[1, \"o\"]\n";
+        assert_eq!(
+            TimedHistory::from_asciicast(bad, 64).err(),
+            Some(ParseError {
+                line: 2,
+                message: "event is not [time, code, data]"
+            })
+        );
+        let deep = format!(
+            "{{\"version\": 2, \"width\": 80, \"height\": 24}}\n{}\n",
+            "[".repeat(200_000)
+        );
+        assert_eq!(
+            TimedHistory::from_asciicast(&deep, 64).err(),
+            Some(ParseError {
+                line: 2,
+                message: "event is not a JSON array"
+            })
+        );
+        for time in ["-1", "1e300"] {
+            let bad = format!(
+                "{{\"version\": 2, \"width\": 80, \"height\": 24}}\n[{time}, \"o\", \"x\"]\n"
+            );
+            assert_eq!(
+                TimedHistory::from_asciicast(&bad, 64).err(),
+                Some(ParseError {
+                    line: 2,
+                    message: "invalid event time"
+                })
+            );
+        }
+    }
+}
@@ -0,0 +1,71 @@
+//! HistoryBufferRef
+//!
+//! A history buffer over storage the caller lends it, such as a block from
+//! an arena or a DMA region, instead of a `Vec` allocated by `new`. The
+//! storage must be a power of two of at least 2 bytes long, so the ring math
+//! stays a mask; `new` returns None for any other length. Like
+//! [`StaticHistoryBuffer`](crate::StaticHistoryBuffer), it has the same
+//! methods as `HistoryBuffer` and needs no `alloc`.
+//!
+//! ```rust
+//! use historybuffer::HistoryBufferRef;
+//!
+//! let mut storage = [0u8; 8];
+//! let mut hb = HistoryBufferRef::new(&mut storage).unwrap();
+//! hb.add(b"The Terminal History.");
+//!
+//! let mut out = [0; 6];
+//! assert_eq!(hb.get_slice_and_index(15, &mut out), (&b"story."[..], 15));
+//! assert_eq!(hb.get_recent_slice(&mut out[..4]), b"ory.");
+//! assert_eq!(hb.get_index(), 13);
+//!
+//! assert!(HistoryBufferRef::new(&mut [0u8; 10]).is_none());
+//! ```
+
+use crate::HistoryRing;
+
+/// A [`HistoryRing`] over storage borrowed from the caller.
+pub type HistoryBufferRef<'a> = HistoryRing<&'a mut [u8]>;
+
+impl<'a> HistoryRing<&'a mut [u8]> {
+    pub fn new(storage: &'a mut [u8]) -> Option<Self> {
+        (storage.len() >= 2 && storage.len().is_power_of_two()).then_some(Self {
+            buf: storage,
+            len: 0,
+            next_running: 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_history_buffer_ref() {
+        let mut storage = [b'-'; 8];
+        let mut hb = HistoryBufferRef::new(&mut storage).unwrap();
+        let mut out = [0; 16];
+        hb.add(b"The Terminal History.");
+        hb.add(b" and");
+        assert_eq!(hb.get_slice_and_index(0, &mut out), (&[][..], 0));
+        assert_eq!(hb.get_slice_and_index(17, &mut out), (&b"ory. and"[..], 17));
+        assert_eq!(hb.as_slices(), (&b"ory. an"[..], &b"d"[..]));
+        assert_eq!(hb.get(16), None);
+        assert_eq!(hb.last_byte(), Some(b'd'));
+        hb.clear_at(19);
+        assert_eq!(hb.get_recent_slice(&mut out), b"y. and");
+        #[cfg(feature = "alloc")]
+        {
+            assert_eq!(hb.get_vec_and_index(0, 100), (b"y. and".to_vec(), 19));
+            assert_eq!(hb.get_vec(20, 2), b". ");
+        }
+
+        println!("        invalid storage:");
+        assert!(HistoryBufferRef::new(&mut [0; 1023]).is_none());
+        assert!(HistoryBufferRef::new(&mut [0; 1]).is_none());
+        assert!(HistoryBufferRef::new(&mut []).is_none());
+        assert!(HistoryBufferRef::new(&mut [0; 2]).is_some());
+    }
+}
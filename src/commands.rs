@@ -0,0 +1,243 @@
+//! CommandTracker
+//!
+//! Splits terminal history into shell commands using the OSC 133 semantic
+//! prompt marks emitted by modern shells:
+//!
+//! * `ESC ] 133 ; A ST` - prompt start
+//! * `ESC ] 133 ; B ST` - command line start (end of prompt)
+//! * `ESC ] 133 ; C ST` - output start (command executed)
+//! * `ESC ] 133 ; D [; exit status] ST` - command finished
+//!
+//! Like [`PlainView`](crate::ansi::PlainView), the tracker is updated after
+//! data is added and only parses the new bytes.
+//!
+//! ```rust
+//! use historybuffer::{CommandTracker, HistoryBuffer};
+//!
+//! let mut hb = HistoryBuffer::new(256);
+//! let mut commands = CommandTracker::new();
+//! hb.add(b"\x1b]133;A\x07$ \x1b]133;B\x07ls\n\x1b]133;C\x07a.txt\n\x1b]133;D;0\x07");
+//! hb.add(b"\x1b]133;A\x07$ ");
+//! commands.update(&hb);
+//!
+//! assert_eq!(commands.last_command_output(&hb), Some(b"a.txt\n".to_vec()));
+//! assert_eq!(commands.commands().next().unwrap().exit_status, Some(0));
+//! ```
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::HistoryBuffer;
+use crate::ansi::{Event, Parser};
+
+/// One shell command, as absolute index ranges into the history.
+///
+/// Stages that have not been seen are empty ranges. The ranges exclude the
+/// OSC 133 marks themselves, and may start before the oldest byte still in
+/// the history.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Command {
+    pub prompt: Range<usize>,
+    pub command_line: Range<usize>,
+    pub output: Range<usize>,
+    /// Exit status from the `D` mark, if the shell reported one.
+    pub exit_status: Option<i32>,
+    /// False while the command is still running or being typed.
+    pub finished: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Marks {
+    prompt: Option<Range<usize>>,
+    command: Option<Range<usize>>,
+    output: Option<Range<usize>>,
+    end: Option<usize>,
+    exit_status: Option<i32>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CommandTracker {
+    parser: Parser,
+    scanned: usize,
+    commands: VecDeque<Marks>,
+}
+
+impl CommandTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// update
+    ///
+    /// Parses bytes added since the last update and drops commands that have
+    /// been entirely evicted.
+    pub fn update(&mut self, hb: &HistoryBuffer) {
+        if self.scanned < hb.get_index() {
+            self.parser = Parser::new();
+            self.scanned = hb.get_index();
+        }
+        let (data, inn) = hb.get_vec_and_index(self.scanned, hb.next_running - self.scanned);
+        for (i, &byte) in data.iter().enumerate() {
+            if let Some((range, Event::Osc(payload))) = self.parser.feed(inn + i, byte) {
+                self.mark(range, &payload);
+            }
+        }
+        self.scanned = hb.next_running;
+
+        let oldest = hb.get_index();
+        while self
+            .commands
+            .front()
+            .and_then(|c| c.end)
+            .is_some_and(|end| end <= oldest)
+        {
+            self.commands.pop_front();
+        }
+    }
+
+    /// commands
+    ///
+    /// Iterates the known commands, oldest first.
+    pub fn commands(&self) -> impl DoubleEndedIterator<Item = Command> + '_ {
+        self.commands.iter().map(|marks| self.to_command(marks))
+    }
+
+    /// last_command
+    ///
+    /// Gets the most recent command that has started producing output.
+    pub fn last_command(&self) -> Option<Command> {
+        self.commands
+            .iter()
+            .rev()
+            .find(|marks| marks.output.is_some())
+            .map(|marks| self.to_command(marks))
+    }
+
+    /// last_command_output
+    ///
+    /// Returns the output of the most recent command as plain text, with
+    /// escape sequences removed.
+    pub fn last_command_output(&self, hb: &HistoryBuffer) -> Option<Vec<u8>> {
+        let output = self.last_command()?.output;
+        Some(hb.get_plain_vec(output.start, output.len()))
+    }
+
+    fn mark(&mut self, range: Range<usize>, payload: &[u8]) {
+        let mut fields = payload.split(|&b| b == b';');
+        if fields.next() != Some(b"133") {
+            return;
+        }
+        match fields.next().unwrap_or_default() {
+            b"A" => {
+                self.finish(range.start, None);
+                self.commands.push_back(Marks {
+                    prompt: Some(range),
+                    ..Default::default()
+                });
+            }
+            b"B" => self.current().command = Some(range),
+            b"C" => self.current().output = Some(range),
+            b"D" => {
+                let exit_status = fields
+                    .next()
+                    .and_then(|status| core::str::from_utf8(status).ok())
+                    .and_then(|status| status.parse().ok());
+                self.finish(range.start, exit_status);
+            }
+            _ => {}
+        }
+    }
+
+    // Gets the open command, starting one if the shell skipped the prompt mark.
+    fn current(&mut self) -> &mut Marks {
+        if self.commands.back().is_none_or(|marks| marks.end.is_some()) {
+            self.commands.push_back(Marks::default());
+        }
+        self.commands.back_mut().unwrap()
+    }
+
+    // Ends the current command at index, if it is still open.
+    fn finish(&mut self, index: usize, exit_status: Option<i32>) {
+        if let Some(current) = self.commands.back_mut()
+            && current.end.is_none()
+        {
+            current.end = Some(index);
+            current.exit_status = exit_status;
+        }
+    }
+
+    fn to_command(&self, marks: &Marks) -> Command {
+        let end = marks.end.unwrap_or(self.scanned);
+        let starts =
+            [&marks.prompt, &marks.command, &marks.output].map(|r| r.as_ref().map(|r| r.start));
+        // Each stage runs from the end of its mark to the start of the next mark seen
+        let stage = |i: usize, mark: &Option<Range<usize>>| {
+            let stop = starts[i + 1..]
+                .iter()
+                .flatten()
+                .next()
+                .copied()
+                .unwrap_or(end);
+            match mark {
+                Some(r) => r.end..stop,
+                None => stop..stop,
+            }
+        };
+        Command {
+            prompt: stage(0, &marks.prompt),
+            command_line: stage(1, &marks.command),
+            output: stage(2, &marks.output),
+            exit_status: marks.exit_status,
+            finished: marks.end.is_some(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_commands() {
+        let mut hb = HistoryBuffer::new(64);
+        let mut tracker = CommandTracker::new();
+        hb.add(b"\x1b]133;A\x07$ \x1b]133;B\x07false\n\x1b]133;C\x07");
+        tracker.update(&hb);
+
+        println!("        running:");
+        let running = tracker.last_command().unwrap();
+        assert!(!running.finished);
+        assert_eq!(
+            hb.get_vec(running.command_line.start, running.command_line.len()),
+            b"false\n"
+        );
+        assert_eq!(running.output, 32..32);
+
+        hb.add(b"boom\n\x1b]133;D;1\x1b\\\x1b]133;A\x07$ ");
+        tracker.update(&hb);
+
+        println!("        finished:");
+        let commands: Vec<Command> = tracker.commands().collect();
+        assert_eq!(commands.len(), 2);
+        assert_eq!(
+            commands[0],
+            Command {
+                prompt: 8..10,
+                command_line: 18..24,
+                output: 32..37,
+                exit_status: Some(1),
+                finished: true,
+            }
+        );
+        assert_eq!(commands[1].prompt, 56..58);
+        assert_eq!(tracker.last_command_output(&hb), Some(b"boom\n".to_vec()));
+
+        println!("        evicted:");
+        hb.add(&[b'x'; 64]);
+        tracker.update(&hb);
+        assert_eq!(tracker.commands().count(), 1);
+        assert_eq!(tracker.last_command(), None);
+    }
+}
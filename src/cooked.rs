@@ -0,0 +1,215 @@
+//! Cooked lines
+//!
+//! Progress bars and spinners redraw a line with carriage returns,
+//! backspaces and erase-in-line, so the raw history holds every
+//! intermediate state. The cooked view applies those edits and yields each
+//! line's final visible content, along with the raw range it came from.
+//!
+//! ```rust
+//! use historybuffer::HistoryBuffer;
+//!
+//! let mut hb = HistoryBuffer::new(64);
+//! hb.add(b"Downloading 10%\rDownloading 90%\r\x1b[KDone\n$ ");
+//!
+//! let lines = hb.get_cooked_lines(0, 100);
+//! assert_eq!(lines[0].text, "Done");
+//! assert_eq!(lines[0].range, 0..40);
+//! assert_eq!(lines[1].text, "$");
+//! assert!(!lines[1].complete);
+//! ```
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::HistoryBuffer;
+use crate::ansi::{self, Event};
+use crate::utf8::Utf8Decoder;
+use crate::wrap::TAB_WIDTH;
+
+// Cursor movement never goes further right than this or the end of the text,
+// so a few bytes of escapes cannot blow a line up to megabytes of blanks.
+const MAX_COLUMN: usize = 4096;
+
+/// A line after carriage return, backspace and erase edits were applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CookedLine {
+    /// Raw bytes the line was drawn from, including its newline.
+    pub range: Range<usize>,
+    /// The final visible text, without trailing blanks.
+    pub text: String,
+    /// False for the last line when it has no newline yet.
+    pub complete: bool,
+}
+
+#[derive(Default)]
+struct LineState {
+    start: usize,
+    cells: Vec<char>,
+    col: usize,
+}
+
+impl LineState {
+    fn put(&mut self, c: char) {
+        if self.col >= self.cells.len() {
+            self.cells.resize(self.col, ' ');
+            self.cells.push(c);
+        } else {
+            self.cells[self.col] = c;
+        }
+        self.col += 1;
+    }
+
+    fn move_to(&mut self, col: usize) {
+        self.col = col.min(self.cells.len().max(MAX_COLUMN));
+    }
+
+    fn csi(&mut self, csi: &ansi::Csi) {
+        if csi.private().is_some() {
+            return;
+        }
+        let n = csi.param(0, 1) as usize;
+        match csi.final_byte {
+            b'C' => self.move_to(self.col.saturating_add(n)),
+            b'D' => self.col = self.col.saturating_sub(n),
+            b'G' => self.move_to(n - 1),
+            b'K' => match csi.param(0, 0) {
+                0 => self.cells.truncate(self.col),
+                1 => {
+                    let end = (self.col + 1).min(self.cells.len());
+                    self.cells[..end].fill(' ');
+                }
+                2 => self.cells.clear(),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn finish(&self, end: usize, complete: bool) -> CookedLine {
+        let text: String = self.cells.iter().collect();
+        CookedLine {
+            range: self.start..end,
+            text: text.trim_end().to_string(),
+            complete,
+        }
+    }
+}
+
+impl HistoryBuffer {
+    /// get_cooked_lines
+    ///
+    /// Returns the cooked lines overlapping a range of history, oldest first.
+    ///
+    /// Note: The first line starts at the oldest byte still in the buffer,
+    /// which may be part way through a line whose start was evicted.
+    pub fn get_cooked_lines(&self, start_index: usize, max_len: usize) -> Vec<CookedLine> {
+        let end_index = start_index.saturating_add(max_len);
+        // Only the lines overlapping the range are parsed
+        let from = ansi::sync_point(self, line_start(self, start_index));
+        let to = line_end(self, end_index.saturating_sub(1).max(from));
+        let mut lines = Vec::new();
+        let mut line = LineState {
+            start: from,
+            ..Default::default()
+        };
+        let mut decoder = Utf8Decoder::new();
+
+        ansi::scan(self, from, to, |range, event| {
+            if line.start >= end_index {
+                return;
+            }
+            match event {
+                Event::Print(b) => decoder.feed(b, |c| line.put(c)),
+                Event::Control(b'\n') => {
+                    let cooked = line.finish(range.end, true);
+                    if cooked.range.end > start_index {
+                        lines.push(cooked);
+                    }
+                    line = LineState {
+                        start: range.end,
+                        ..Default::default()
+                    };
+                }
+                Event::Control(b'\r') => line.col = 0,
+                Event::Control(0x08) => line.col = line.col.saturating_sub(1),
+                Event::Control(b'\t') => line.move_to((line.col / TAB_WIDTH + 1) * TAB_WIDTH),
+                Event::Csi(csi) => line.csi(&csi),
+                _ => {}
+            }
+        });
+
+        let last = line.finish(to, false);
+        if last.range.start < end_index && last.range.end > start_index && !last.range.is_empty() {
+            lines.push(last);
+        }
+        lines
+    }
+}
+
+/// Start of the line containing index.
+pub(crate) fn line_start(hb: &HistoryBuffer, index: usize) -> usize {
+    let oldest = hb.get_index();
+    let mut i = index.min(hb.next_running);
+    while i > oldest && hb.get(i - 1) != Some(b'\n') {
+        i -= 1;
+    }
+    i.max(oldest)
+}
+
+/// End of the line containing index, just past its newline.
+pub(crate) fn line_end(hb: &HistoryBuffer, index: usize) -> usize {
+    (index.max(hb.get_index())..hb.next_running)
+        .find(|&i| hb.get(i) == Some(b'\n'))
+        .map_or(hb.next_running, |i| i + 1)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_cooked_lines() {
+        let mut hb = HistoryBuffer::new(128);
+        hb.add(b"Compiling [=>  ]\r");
+        hb.add(b"Compiling [==>]\r\x1b[2KFinished\n");
+        hb.add(b"typo\x08\x08\x08\x08type\n");
+        hb.add(b"50%\x1b[3D100%\tok\n");
+
+        let lines = hb.get_cooked_lines(0, 1000);
+        assert_eq!(
+            lines.iter().map(|l| l.text.as_str()).collect::<Vec<&str>>(),
+            vec!["Finished", "type", "100%    ok"]
+        );
+        assert_eq!(lines[1].range, 46..59);
+        assert!(lines.iter().all(|l| l.complete));
+
+        println!("        range selects overlapping lines:");
+        let lines = hb.get_cooked_lines(50, 10);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].range.start, 46);
+        assert_eq!(hb.get_cooked_lines(59, 0), vec![]);
+
+        println!("        parsing starts near the range, not at the oldest byte:");
+        let mut hb = HistoryBuffer::new(64);
+        hb.add(b"one\n\x1b]0;t\nt\x07two\nthree\n");
+        assert_eq!(
+            hb.get_cooked_lines(12, 1),
+            vec![CookedLine {
+                range: 4..16,
+                text: "two".to_string(),
+                complete: true
+            }]
+        );
+
+        println!("        cursor movement is bounded:");
+        let mut hb = HistoryBuffer::new(1 << 16);
+        for _ in 0..2000 {
+            hb.add(b"\x1b[65535C");
+        }
+        hb.add(b"x\x1b[99999Gy\n");
+        let lines = hb.get_cooked_lines(0, 1 << 16);
+        assert_eq!(lines[0].text, " ".repeat(MAX_COLUMN) + "xy");
+    }
+}
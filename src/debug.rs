@@ -0,0 +1,221 @@
+//! Debug output
+//!
+//! `{:?}` shows the logical state of any [`HistoryRing`], under the name of
+//! its alias: capacity, indices, length and an escaped preview of the newest
+//! bytes.
+//! Bytes that are no longer part of the history are never shown.
+//!
+//! `{:#?}` adds the physical ring: `|` marks the slot the next byte will be
+//! written to, so the oldest live byte is right after it once the buffer is
+//! full. Slots holding no history are shown as `·`.
+//!
+//! ```rust
+//! # #[cfg(feature = "alloc")] {
+//! use historybuffer::HistoryBuffer;
+//!
+//! let mut hb = HistoryBuffer::new(8);
+//! hb.add(b"The Terminal History.");
+//! assert_eq!(
+//!     format!("{hb:?}"),
+//!     r#"HistoryBuffer { capacity: 8, get_index: 13, get_last_index: 20, len: 8, recent: "History." }"#
+//! );
+//! # }
+//! ```
+
+use core::fmt;
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use crate::{HistoryRing, ring};
+
+const PREVIEW_LEN: usize = 32;
+
+// Escaped bytes in quotes, with ... in front if older bytes were left out.
+struct Preview<'a> {
+    bytes: (&'a [u8], &'a [u8]),
+    truncated: bool,
+}
+
+impl fmt::Debug for Preview<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let dots = if self.truncated { "..." } else { "" };
+        let (first, second) = self.bytes;
+        write!(
+            f,
+            "\"{dots}{}{}\"",
+            first.escape_ascii(),
+            second.escape_ascii()
+        )
+    }
+}
+
+// The state shown by Debug, whatever owns the storage.
+#[derive(Clone, Copy)]
+struct View<'a> {
+    buf: &'a [u8],
+    len: usize,
+    next_running: usize,
+}
+
+impl View<'_> {
+    fn mask(&self) -> usize {
+        self.buf.len().saturating_sub(1)
+    }
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>, name: &str) -> fmt::Result {
+        let alternate = f.alternate();
+        let shown = self.len.min(PREVIEW_LEN);
+        let index = self.next_running - self.len;
+        let mut s = f.debug_struct(name);
+        s.field("capacity", &self.buf.len())
+            .field("get_index", &index)
+            .field(
+                "get_last_index",
+                &self.next_running.saturating_sub(1).max(index),
+            )
+            .field("len", &self.len)
+            .field(
+                "recent",
+                &Preview {
+                    bytes: ring::slices(self.buf, self.next_running - shown..self.next_running),
+                    truncated: self.len > shown,
+                },
+            );
+        if alternate {
+            s.field("wrap_at", &(self.next_running & self.mask()))
+                .field("ring", &Ring(*self));
+        }
+        s.finish()
+    }
+}
+
+// The physical slots around the write position.
+struct Ring<'a>(View<'a>);
+
+impl fmt::Debug for Ring<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let view = self.0;
+        let capacity = view.buf.len();
+        let mask = view.mask();
+        let wrap_at = view.next_running & mask;
+        let cut = capacity > 2 * PREVIEW_LEN;
+        let (first, last) = if cut {
+            (
+                wrap_at as isize - PREVIEW_LEN as isize,
+                wrap_at + PREVIEW_LEN,
+            )
+        } else {
+            (0, capacity)
+        };
+        f.write_str(if cut { "\"..." } else { "\"" })?;
+        for slot in first..last as isize {
+            let slot = slot.rem_euclid(capacity as isize) as usize;
+            if slot == wrap_at {
+                f.write_str("|")?;
+            }
+            // Newest index stored in this slot
+            let index = view
+                .next_running
+                .checked_sub(1 + (view.next_running.wrapping_sub(1 + slot) & mask));
+            match index.and_then(|i| ring::get(view.buf, view.next_running, view.len, i)) {
+                Some(byte) => write!(f, "{}", [byte].escape_ascii())?,
+                None => f.write_str("·")?,
+            }
+        }
+        f.write_str(if cut { "...\"" } else { "\"" })
+    }
+}
+
+impl<S: AsRef<[u8]>> HistoryRing<S> {
+    fn view(&self) -> View<'_> {
+        View {
+            buf: self.buf.as_ref(),
+            len: self.len,
+            next_running: self.next_running,
+        }
+    }
+}
+
+// Each storage is shown under the name of its alias.
+#[cfg(feature = "alloc")]
+impl fmt::Debug for HistoryRing<Vec<u8>> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.view().fmt(f, "HistoryBuffer")
+    }
+}
+
+impl<const N: usize> fmt::Debug for HistoryRing<[u8; N]> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.view().fmt(f, "StaticHistoryBuffer")
+    }
+}
+
+impl fmt::Debug for HistoryRing<&mut [u8]> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.view().fmt(f, "HistoryBufferRef")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_debug() {
+        use crate::HistoryBuffer;
+
+        let mut hb = HistoryBuffer::new(8);
+        hb.add(b"abc\n");
+        assert_eq!(
+            format!("{hb:?}"),
+            r#"HistoryBuffer { capacity: 8, get_index: 0, get_last_index: 3, len: 4, recent: "abc\n" }"#
+        );
+        assert_eq!(
+            format!("{hb:#?}"),
+            concat!(
+                "HistoryBuffer {\n",
+                "    capacity: 8,\n",
+                "    get_index: 0,\n",
+                "    get_last_index: 3,\n",
+                "    len: 4,\n",
+                "    recent: \"abc\\n\",\n",
+                "    wrap_at: 4,\n",
+                "    ring: \"abc\\n|····\",\n",
+                "}"
+            )
+        );
+
+        println!("        wrapped, and cleared before index 8:");
+        hb.add(b"defgh");
+        hb.clear_at(8);
+        assert!(format!("{hb:#?}").contains(r#"ring: "h|·······","#));
+
+        println!("        large buffers are cut around the wrap point:");
+        let mut hb = HistoryBuffer::new(256);
+        hb.add(&[b'x'; 300]);
+        let text = format!("{hb:#?}");
+        assert!(text.contains(r#"recent: "...xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx","#));
+        assert!(text.contains(&format!(
+            "ring: \"...{}|{}...\"",
+            "x".repeat(32),
+            "x".repeat(32)
+        )));
+    }
+
+    #[test]
+    fn test_ring_debug() {
+        let mut hb = HistoryRing::<[u8; 4]>::new();
+        hb.add(b"abcde");
+        assert_eq!(
+            format!("{hb:?}"),
+            r#"StaticHistoryBuffer { capacity: 4, get_index: 1, get_last_index: 4, len: 4, recent: "bcde" }"#
+        );
+        let mut storage = [0; 2];
+        let mut hb = HistoryRing::<&mut [u8]>::new(&mut storage).unwrap();
+        hb.add(b"x");
+        assert!(format!("{hb:#?}").contains(r#"ring: "x|·","#));
+    }
+}
@@ -0,0 +1,139 @@
+//! Hexdump
+//!
+//! A `Display` adapter that renders a range of history in the canonical
+//! `hexdump -C` layout: offset, hex bytes and an ASCII gutter. Offsets are
+//! absolute indices, the same numbers `get_vec_and_index` uses, and bytes
+//! are read one at a time while formatting, so nothing is copied up front.
+//!
+//! ```rust
+//! use historybuffer::HistoryBuffer;
+//!
+//! let mut hb = HistoryBuffer::new(8);
+//! hb.add(b"\x00\x01\x02AT+OK\r\n");
+//!
+//! assert_eq!(
+//!     hb.hexdump(0..100).width(4).to_string(),
+//!     "00000002  02 41 54 2b  |.AT+|\n\
+//!      00000006  4f 4b 0d 0a  |OK..|\n"
+//! );
+//! ```
+
+use core::fmt;
+use core::ops::Range;
+
+use crate::HistoryBuffer;
+
+const DEFAULT_WIDTH: usize = 16;
+const DEFAULT_GROUP: usize = 8;
+
+/// Formats a range of history as a hexdump. Made by `HistoryBuffer::hexdump`.
+#[derive(Debug, Clone)]
+pub struct Hexdump<'a> {
+    hb: &'a HistoryBuffer,
+    range: Range<usize>,
+    width: usize,
+    group: usize,
+}
+
+impl HistoryBuffer {
+    /// hexdump
+    ///
+    /// Returns a `Display` adapter for the bytes in range that are still in
+    /// memory, 16 bytes per line in groups of 8.
+    pub fn hexdump(&self, range: Range<usize>) -> Hexdump<'_> {
+        Hexdump {
+            hb: self,
+            range,
+            width: DEFAULT_WIDTH,
+            group: DEFAULT_GROUP,
+        }
+    }
+}
+
+impl Hexdump<'_> {
+    /// width
+    ///
+    /// Sets the number of bytes per line.
+    pub fn width(mut self, width: usize) -> Self {
+        self.width = width.max(1);
+        self
+    }
+
+    /// group
+    ///
+    /// Sets how many bytes go between the extra spaces in the hex column.
+    /// 0 turns grouping off.
+    pub fn group(mut self, group: usize) -> Self {
+        self.group = group;
+        self
+    }
+}
+
+impl fmt::Display for Hexdump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let start = self.range.start.max(self.hb.get_index());
+        let end = self.range.end.min(self.hb.next_running);
+        let group = if self.group == 0 {
+            self.width
+        } else {
+            self.group
+        };
+        let mut row = start;
+        while row < end {
+            let row_end = row.saturating_add(self.width).min(end);
+            write!(f, "{row:08x}")?;
+            for i in 0..self.width {
+                if i % group == 0 {
+                    f.write_str(" ")?;
+                }
+                match self.hb.get(row + i).filter(|_| row + i < row_end) {
+                    Some(byte) => write!(f, " {byte:02x}")?,
+                    None => f.write_str("   ")?,
+                }
+            }
+            f.write_str("  |")?;
+            for i in row..row_end {
+                let byte = self.hb.get(i).unwrap_or(b'.');
+                let c = if byte.is_ascii_graphic() || byte == b' ' {
+                    byte as char
+                } else {
+                    '.'
+                };
+                write!(f, "{c}")?;
+            }
+            f.write_str("|\n")?;
+            row = row_end;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_hexdump() {
+        let mut hb = HistoryBuffer::new(32);
+        hb.add(b"Hello, world!\n\xff\x00 tail");
+
+        assert_eq!(
+            hb.hexdump(0..100).to_string(),
+            "00000000  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 0a ff 00  |Hello, world!...|\n\
+             00000010  20 74 61 69 6c                                    | tail|\n"
+        );
+
+        println!("        width and group:");
+        assert_eq!(
+            hb.hexdump(7..13).width(4).group(2).to_string(),
+            "00000007  77 6f  72 6c  |worl|\n\
+             0000000b  64 21         |d!|\n"
+        );
+        assert_eq!(
+            hb.hexdump(3..5).group(0).width(3).to_string(),
+            "00000003  6c 6f     |lo|\n"
+        );
+        assert_eq!(hb.hexdump(30..40).to_string(), "");
+    }
+}
@@ -0,0 +1,353 @@
+//! HTML export
+//!
+//! `HistoryBuffer::to_html` renders a range of history as a standalone HTML
+//! document for sharing logs. SGR colors and attributes become styled
+//! `<span>`s, other escape sequences are dropped and text is escaped.
+//!
+//! SGR state is tracked from the oldest byte in the buffer, so a range that
+//! starts after a color was set still comes out in that color. A sequence
+//! whose head has been evicted is lost, and its tail shows up as text.
+//!
+//! ```rust
+//! use historybuffer::HistoryBuffer;
+//!
+//! let mut hb = HistoryBuffer::new(64);
+//! hb.add(b"\x1b[1;31mfailed:\x1b[0m a < b\n");
+//!
+//! let html = hb.to_html(0..100);
+//! assert!(html.contains(r#"<span style="color:#cd0000;font-weight:bold">failed:</span> a &lt; b"#));
+//! ```
+//!
+//! [`SgrTracker`] follows the stream as it arrives and remembers the
+//! attributes set by each escape sequence, so its `to_html` keeps the colors
+//! after the sequence that set them is evicted, and skips the tail of a
+//! sequence whose head is gone. It also starts parsing at the last sequence
+//! before the range instead of at the oldest byte.
+//!
+//! ```rust
+//! use historybuffer::{HistoryBuffer, SgrTracker};
+//!
+//! let mut hb = HistoryBuffer::new(16);
+//! let mut sgr = SgrTracker::new();
+//! hb.add(b"\x1b[35mmagenta ");
+//! sgr.update(&hb);
+//! hb.add(b"text");
+//! sgr.update(&hb);
+//!
+//! assert!(sgr.to_html(&hb, 0..100).contains(r#"<span style="color:#cd00cd">magenta text</span>"#));
+//! ```
+
+use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::ops::Range;
+
+use crate::HistoryBuffer;
+use crate::ansi::{Attrs, Color, Event, Parser};
+use crate::utf8::Utf8Decoder;
+
+const DEFAULT_FG: (u8, u8, u8) = (0xe5, 0xe5, 0xe5);
+const DEFAULT_BG: (u8, u8, u8) = (0x00, 0x00, 0x00);
+
+// xterm's 16 standard and bright colors.
+const PALETTE: [(u8, u8, u8); 16] = [
+    (0x00, 0x00, 0x00),
+    (0xcd, 0x00, 0x00),
+    (0x00, 0xcd, 0x00),
+    (0xcd, 0xcd, 0x00),
+    (0x00, 0x00, 0xee),
+    (0xcd, 0x00, 0xcd),
+    (0x00, 0xcd, 0xcd),
+    (0xe5, 0xe5, 0xe5),
+    (0x7f, 0x7f, 0x7f),
+    (0xff, 0x00, 0x00),
+    (0x00, 0xff, 0x00),
+    (0xff, 0xff, 0x00),
+    (0x5c, 0x5c, 0xff),
+    (0xff, 0x00, 0xff),
+    (0x00, 0xff, 0xff),
+    (0xff, 0xff, 0xff),
+];
+
+impl HistoryBuffer {
+    /// to_html
+    ///
+    /// Renders the history in range as an HTML document, keeping SGR colors,
+    /// bold, dim, italic, underline and strikethrough.
+    pub fn to_html(&self, range: Range<usize>) -> String {
+        render(self, self.get_index(), Attrs::default(), range)
+    }
+}
+
+/// SgrTracker
+///
+/// Follows SGR state as output arrives. Call `update` after adding to the
+/// history; only the new bytes are parsed.
+#[derive(Debug, Clone, Default)]
+pub struct SgrTracker {
+    parser: Parser,
+    scanned: usize,
+    attrs: Attrs,
+    base: Attrs, // In effect before the oldest sequence kept
+    sequences: VecDeque<(Range<usize>, Attrs)>, // Attributes after each sequence
+}
+
+impl SgrTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// update
+    ///
+    /// Parses bytes added since the last update and forgets sequences that
+    /// ended before the oldest byte, keeping the attributes they left.
+    pub fn update(&mut self, hb: &HistoryBuffer) {
+        if self.scanned < hb.get_index() {
+            // Missed bytes were evicted before we saw them
+            *self = Self::new();
+            self.scanned = hb.get_index();
+        }
+        let (data, inn) = hb.get_vec_and_index(self.scanned, hb.next_running - self.scanned);
+        for (i, &byte) in data.iter().enumerate() {
+            match self.parser.feed(inn + i, byte) {
+                Some((_, Event::Print(_) | Event::Control(_))) | None => {}
+                Some((range, event)) => {
+                    if let Event::Csi(csi) = &event {
+                        self.attrs.apply_sgr(csi);
+                    }
+                    self.sequences.push_back((range, self.attrs));
+                }
+            }
+        }
+        self.scanned = hb.next_running;
+
+        while let Some(&(ref range, attrs)) = self.sequences.front()
+            && range.end <= hb.get_index()
+        {
+            self.base = attrs;
+            self.sequences.pop_front();
+        }
+    }
+
+    /// to_html
+    ///
+    /// Like `HistoryBuffer::to_html`, but with the attributes in effect at
+    /// the oldest byte even after the sequence that set them was evicted.
+    /// hb must be the history this tracker was updated from.
+    pub fn to_html(&self, hb: &HistoryBuffer, range: Range<usize>) -> String {
+        let oldest = hb.get_index();
+        let from = range.start.max(oldest);
+        let n = self.sequences.partition_point(|(seq, _)| seq.end <= from);
+        let (mut start, mut attrs) = match n.checked_sub(1) {
+            Some(i) => (self.sequences[i].0.end, self.sequences[i].1),
+            None => (oldest, self.base),
+        };
+        if let Some((seq, after)) = self.sequences.get(n)
+            && seq.start < oldest
+        {
+            // Its head is evicted; skip the tail
+            (start, attrs) = (seq.end, *after);
+        }
+        render(hb, start.max(oldest), attrs, range)
+    }
+}
+
+// Renders range, parsing from start, which must be outside any sequence,
+// with attrs in effect there.
+fn render(hb: &HistoryBuffer, start: usize, mut attrs: Attrs, range: Range<usize>) -> String {
+    let mut body = String::new();
+    let mut open: Option<Attrs> = None; // Attributes of the open span
+    let mut parser = Parser::new();
+    let mut decoder = Utf8Decoder::new();
+
+    let (data, inn) = hb.get_vec_and_index(start, range.end.saturating_sub(start));
+    for (i, &byte) in data.iter().enumerate() {
+        let Some((bytes, event)) = parser.feed(inn + i, byte) else {
+            continue;
+        };
+        if let Event::Csi(csi) = &event {
+            attrs.apply_sgr(csi);
+            continue;
+        }
+        if bytes.start < range.start {
+            continue;
+        }
+        let text = match event {
+            Event::Print(b) => {
+                let mut text = String::new();
+                decoder.feed(b, |c| text.push(c));
+                text
+            }
+            Event::Control(b @ (b'\n' | b'\t')) => (b as char).to_string(),
+            _ => continue,
+        };
+        if text.is_empty() {
+            continue;
+        }
+        if open != Some(attrs) {
+            if open.is_some_and(|a| a != Attrs::default()) {
+                body.push_str("</span>");
+            }
+            if attrs != Attrs::default() {
+                let _ = write!(body, r#"<span style="{}">"#, style(&attrs));
+            }
+            open = Some(attrs);
+        }
+        escape(&mut body, &text);
+    }
+    if open.is_some_and(|a| a != Attrs::default()) {
+        body.push_str("</span>");
+    }
+
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <style>body {{ background-color: {}; color: {}; }}</style>\n\
+         </head>\n<body>\n<pre>{}</pre>\n</body>\n</html>\n",
+        hex(DEFAULT_BG),
+        hex(DEFAULT_FG),
+        body
+    )
+}
+
+fn escape(out: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+}
+
+fn rgb(color: Color, default: (u8, u8, u8)) -> (u8, u8, u8) {
+    match color {
+        Color::Default => default,
+        Color::Rgb(r, g, b) => (r, g, b),
+        Color::Indexed(n @ 0..=15) => PALETTE[n as usize],
+        Color::Indexed(n @ 16..=231) => {
+            let level = |v: u8| if v == 0 { 0 } else { 55 + v * 40 };
+            let n = n - 16;
+            (level(n / 36), level(n / 6 % 6), level(n % 6))
+        }
+        Color::Indexed(n) => {
+            let v = 8 + (n - 232) * 10;
+            (v, v, v)
+        }
+    }
+}
+
+fn hex((r, g, b): (u8, u8, u8)) -> String {
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+fn style(attrs: &Attrs) -> String {
+    let (mut fg, mut bg) = (attrs.fg, attrs.bg);
+    if attrs.inverse {
+        (fg, bg) = (bg, fg);
+    }
+    let mut styles = Vec::new();
+    if fg != Color::Default || attrs.inverse {
+        let default = if attrs.inverse {
+            DEFAULT_BG
+        } else {
+            DEFAULT_FG
+        };
+        styles.push(format!("color:{}", hex(rgb(fg, default))));
+    }
+    if bg != Color::Default || attrs.inverse {
+        let default = if attrs.inverse {
+            DEFAULT_FG
+        } else {
+            DEFAULT_BG
+        };
+        styles.push(format!("background-color:{}", hex(rgb(bg, default))));
+    }
+    if attrs.bold {
+        styles.push("font-weight:bold".to_string());
+    }
+    if attrs.dim {
+        styles.push("opacity:0.5".to_string());
+    }
+    if attrs.italic {
+        styles.push("font-style:italic".to_string());
+    }
+    match (attrs.underline, attrs.strike) {
+        (true, true) => styles.push("text-decoration:underline line-through".to_string()),
+        (true, false) => styles.push("text-decoration:underline".to_string()),
+        (false, true) => styles.push("text-decoration:line-through".to_string()),
+        (false, false) => {}
+    }
+    if attrs.hidden {
+        styles.push("visibility:hidden".to_string());
+    }
+    styles.join(";")
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn body(html: &str) -> &str {
+        let start = html.find("<pre>").unwrap() + 5;
+        &html[start..html.find("</pre>").unwrap()]
+    }
+
+    #[test]
+    fn test_to_html() {
+        let mut hb = HistoryBuffer::new(64);
+        hb.add(b"\x1b[32mok\x1b[0m <&> \x1b[4;38;5;196mx\x1b[24my\x1b[7mz\x1b[m\n");
+
+        assert_eq!(
+            body(&hb.to_html(0..100)),
+            concat!(
+                r#"<span style="color:#00cd00">ok</span> &lt;&amp;&gt; "#,
+                r#"<span style="color:#ff0000;text-decoration:underline">x</span>"#,
+                r#"<span style="color:#ff0000">y</span>"#,
+                r#"<span style="color:#000000;background-color:#ff0000">z</span>"#,
+                "\n"
+            )
+        );
+
+        println!("        color set before the range, and before eviction:");
+        let mut hb = HistoryBuffer::new(16);
+        hb.add(b"\x1b[35mmagenta ");
+        assert_eq!(
+            body(&hb.to_html(5..10)),
+            r#"<span style="color:#cd00cd">magen</span>"#
+        );
+        let mut sgr = SgrTracker::new();
+        sgr.update(&hb);
+        hb.add(b"text");
+        sgr.update(&hb);
+        assert_eq!(hb.get_index(), 1);
+        println!("        evicted ESC: the tracker keeps the color:");
+        assert_eq!(
+            body(&sgr.to_html(&hb, 0..100)),
+            r#"<span style="color:#cd00cd">magenta text</span>"#
+        );
+        assert_eq!(
+            body(&sgr.to_html(&hb, 14..100)),
+            r#"<span style="color:#cd00cd">ext</span>"#
+        );
+        println!("        without it the tail shows as text:");
+        assert_eq!(body(&hb.to_html(0..100)), "[35mmagenta text");
+
+        println!("        whole sequence evicted:");
+        hb.add(b"\x1b[1mbold\x1b[0m!");
+        sgr.update(&hb);
+        assert_eq!(hb.get_index(), 14);
+        assert_eq!(
+            body(&sgr.to_html(&hb, 0..100)),
+            concat!(
+                r#"<span style="color:#cd00cd">ext</span>"#,
+                r#"<span style="color:#cd00cd;font-weight:bold">bold</span>!"#
+            )
+        );
+    }
+}
@@ -12,33 +12,33 @@
 //! The library could be easily extended to other types besides [u8].
 //!
 //! ```rust
+//! # #[cfg(feature = "alloc")] {
 //! use historybuffer::HistoryBuffer;
 //!
-//! fn main() {
-//!     let mut hb = HistoryBuffer::new(6); // Create an 8-element buffer (next power of 2).
+//! let mut hb = HistoryBuffer::new(6); // Create an 8-element buffer (next power of 2).
 //!
-//!     hb.add("The Terminal History.".to_string().as_bytes());
-//!     assert_eq!(
-//!         hb.get_vec(15, 6),
-//!         "story.".to_string().as_bytes().to_vec()
-//!     );
+//! hb.add("The Terminal History.".to_string().as_bytes());
+//! assert_eq!(
+//!     hb.get_vec(15, 6),
+//!     "story.".to_string().as_bytes().to_vec()
+//! );
 //!
-//!     assert_eq!(hb.last_byte(), Some(b'.'));
+//! assert_eq!(hb.last_byte(), Some(b'.'));
 //!
-//!     assert_eq!(hb.get_recent(4), "ory.".to_string().as_bytes());
+//! assert_eq!(hb.get_recent(4), "ory.".to_string().as_bytes());
 //!
-//!     assert_eq!(hb.get_index(), 13);
+//! assert_eq!(hb.get_index(), 13);
 //!
-//!     assert_eq!(hb.get(13), Some(b'H'));
+//! assert_eq!(hb.get(13), Some(b'H'));
 //!
-//!     assert_eq!(hb.get_last_index(), 20);
+//! assert_eq!(hb.get_last_index(), 20);
 //!
-//!     assert_eq!(hb.get(20), Some(b'.'));
+//! assert_eq!(hb.get(20), Some(b'.'));
 //!
-//!     hb.add(" and".to_string().as_bytes());
+//! hb.add(" and".to_string().as_bytes());
 //!
-//!     assert_eq!(hb.get(13), None);
-//! }
+//! assert_eq!(hb.get(13), None);
+//! # }
 //!```
 //!
 //!
@@ -50,6 +50,168 @@
 //!
 //! Note: This code has not been tested for wrapping `usize` values > 4 billion chars 
 //!       from long running apps.
+//!
+// Everything linked here needs the alloc feature
+#![cfg_attr(
+    feature = "alloc",
+    doc = r#"
+[`RecordBuffer`] keeps the boundaries of each `add` call so packets and log
+records can be read back individually. [`HistoryLog`] builds on it to store
+length-prefixed messages that are always evicted whole.
+
+[`MarkedHistory`] attaches bookmarks to absolute indices and drops them as
+their bytes are overwritten. [`TaggedHistory`] attaches arbitrary metadata
+to byte ranges and keeps it in step with eviction. [`MultiStreamHistory`]
+interleaves stdout, stderr and friends into one index space while
+remembering which stream each byte came from.
+
+The [`ansi`] module parses terminal escape sequences; `get_plain_vec`
+returns history with them stripped, and [`ansi::PlainView`] keeps an
+[`OffsetMap`] between the stripped text and raw indices.
+[`CommandTracker`] splits history into shell commands using OSC 133 marks.
+[`Screen`] replays history through a VT100 / xterm emulator to rebuild
+what the terminal showed. `get_cooked_lines` applies carriage returns,
+backspaces and erase-in-line so progress bars collapse to their final text.
+[`WrapView`] soft-wraps history to a terminal width for scrollback, and
+[`Viewport`] keeps a scrolled-up window anchored while output arrives.
+
+`get_str_lossy`, `get_utf8` and `chars_rev` read text without splitting
+UTF-8 characters.
+
+[`Selector`] expands a clicked index to a word, a line or a block of
+columns and extracts the selected text.
+
+[`LinkDetector`] finds URLs, file paths, `path:line:col` references and
+OSC 8 hyperlinks as output arrives.
+
+`to_html` renders a range of history as an HTML document with its colors.
+[`SgrTracker`] keeps those colors right after eviction.
+
+`hexdump` formats a range of binary history like `hexdump -C`, with
+absolute indices as offsets.
+
+`HistoryBuffer` implements `Debug`, showing its indices and newest bytes;
+`{:#?}` also shows where the ring wraps.
+
+Buffers are `Clone`, and compare and hash by their live bytes and indices,
+so two buffers holding the same history are equal whatever their capacity.
+
+With the `serde` feature, `HistoryBuffer` implements `Serialize` and
+`Deserialize`, storing its capacity, starting index and live bytes.
+
+[`TimedHistory`] records when each `add` happened, and can be exported to
+and imported from asciinema's asciicast v2 format.
+"#
+)]
+//!
+//! The crate is `no_std`. The `std` feature, on by default, adds
+//! `std::io::Write` for `HistoryBuffer` and the clock behind
+//! `TimedHistory::add`. Without it, the `alloc` feature still provides
+//! `HistoryBuffer` and everything built on it.
+//!
+//! [`StaticHistoryBuffer`] needs neither: it keeps its bytes in an inline
+//! array of a power-of-two size and can be created in a `static`.
+//! [`HistoryBufferRef`] runs the same ring over a power-of-two `&mut [u8]`
+//! the caller provides, for memory from an arena or a DMA region. These and
+//! `HistoryBuffer` are names for [`HistoryRing`] over different storage, so
+//! they share one implementation.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "alloc")]
+pub mod ansi;
+#[cfg(feature = "alloc")]
+mod asciicast;
+mod borrowed;
+#[cfg(feature = "alloc")]
+mod commands;
+#[cfg(feature = "alloc")]
+mod cooked;
+mod debug;
+#[cfg(feature = "alloc")]
+mod hexdump;
+#[cfg(feature = "alloc")]
+mod html;
+#[cfg(feature = "alloc")]
+mod links;
+#[cfg(feature = "alloc")]
+mod log;
+#[cfg(feature = "alloc")]
+mod markers;
+#[cfg(feature = "alloc")]
+mod offsets;
+#[cfg(feature = "alloc")]
+mod record;
+mod ring;
+#[cfg(feature = "alloc")]
+mod screen;
+#[cfg(feature = "alloc")]
+mod script;
+#[cfg(feature = "alloc")]
+mod selection;
+#[cfg(feature = "serde")]
+mod serde_impl;
+mod static_buffer;
+#[cfg(feature = "alloc")]
+mod streams;
+#[cfg(feature = "alloc")]
+mod tags;
+#[cfg(feature = "alloc")]
+mod timed;
+#[cfg(feature = "alloc")]
+mod utf8;
+#[cfg(feature = "alloc")]
+mod viewport;
+#[cfg(feature = "alloc")]
+mod wrap;
+
+#[cfg(feature = "alloc")]
+pub use asciicast::AsciicastHeader;
+pub use borrowed::HistoryBufferRef;
+#[cfg(feature = "alloc")]
+pub use commands::{Command, CommandTracker};
+#[cfg(feature = "alloc")]
+pub use cooked::CookedLine;
+#[cfg(feature = "alloc")]
+pub use hexdump::Hexdump;
+#[cfg(feature = "alloc")]
+pub use html::SgrTracker;
+#[cfg(feature = "alloc")]
+pub use links::{Link, LinkDetector, LinkKind};
+#[cfg(feature = "alloc")]
+pub use log::{HistoryLog, LogEntry};
+#[cfg(feature = "alloc")]
+pub use markers::{Evicted, MarkedHistory, Marker};
+#[cfg(feature = "alloc")]
+pub use offsets::OffsetMap;
+#[cfg(feature = "alloc")]
+pub use record::{Record, RecordBuffer};
+pub use ring::HistoryRing;
+#[cfg(feature = "alloc")]
+pub use screen::{Cell, Screen};
+#[cfg(feature = "alloc")]
+pub use script::ScriptFormat;
+#[cfg(feature = "alloc")]
+pub use selection::Selector;
+pub use static_buffer::StaticHistoryBuffer;
+#[cfg(feature = "alloc")]
+pub use streams::{MultiStreamHistory, StreamId, StreamRun};
+#[cfg(feature = "alloc")]
+pub use tags::{Span, TaggedHistory};
+#[cfg(feature = "alloc")]
+pub use timed::{ParseError, TimedHistory};
+#[cfg(feature = "alloc")]
+pub use utf8::{CharsRev, char_width};
+#[cfg(feature = "alloc")]
+pub use viewport::Viewport;
+#[cfg(feature = "alloc")]
+pub use wrap::{Row, WrapView};
+
+#[cfg(feature = "alloc")]
+use alloc::{vec, vec::Vec};
 
 
 //
@@ -64,211 +226,49 @@
 // oldest byte's index if the buffer is full.
 //
 
-#[derive(Default)]
-pub struct HistoryBuffer {
-    buf: Vec<u8>,
-    len: usize,
-    mask: usize,
-    next_running: usize,
-}
+/// A history ring in a `Vec` of at least the requested size, rounded up to a
+/// power of two.
+#[cfg(feature = "alloc")]
+pub type HistoryBuffer = HistoryRing<Vec<u8>>;
 
-impl HistoryBuffer {
+#[cfg(feature = "alloc")]
+impl HistoryRing<Vec<u8>> {
     pub fn new(min_buf_size: usize) -> Self {
         let power_two_size = next_power_of_two(min_buf_size).clamp(2, 1 << 23);
         Self {
             buf: vec![0; power_two_size],
-            mask: power_two_size - 1,
-            ..Default::default()
-        }
-    }
-
-    /// add
-    ///
-    /// This function ingests data slices and copies them to the internal buffer.
-    pub fn add(&mut self, data: &[u8]) {
-        if data.is_empty() {
-            return;
-        }
-
-        let buf_size = self.buf.len();
-        self.next_running += data.len();
-        self.len = (self.len + data.len()).min(buf_size);
-        let inndx = (self.next_running - data.len().min(buf_size)) & self.mask;
-        let outdx = self.next_running & self.mask;
-
-        if outdx > inndx {
-            let num = outdx.saturating_sub(inndx);
-            self.buf[inndx..outdx].copy_from_slice(&data[0..num]);
-        } else {
-            let num = (outdx + buf_size).saturating_sub(inndx);
-            let wrap = data.len().saturating_sub(outdx);
-            self.buf[inndx..buf_size].copy_from_slice(&data[(data.len() - num)..wrap]);
-            self.buf[0..outdx].copy_from_slice(&data[wrap..data.len()]);
+            len: 0,
+            next_running: 0,
         }
     }
+}
 
-    /// clear the buffer
-    ///
-    /// Clear the len - Data is still there, but access is denied.
-    pub fn clear(&mut self) {
-        self.len = 0;
-    }
-
-    /// clear_at the buffer at a specific point
-    ///
-    /// Set the len to erase all history before start_index.
-    /// Data is still there, but access is denied.
-    pub fn clear_at(&mut self, new_start_index: usize) {
-        self.len = self.next_running.saturating_sub(new_start_index).min(self.len);
-    }
-
-    /// get
-    ///
-    /// Gets the byte value at index.
-    pub fn get(&self, index: usize) -> Option<u8> {
-        if (index >= self.next_running - self.len) && (index < self.next_running) {
-            Some(self.buf[index & self.mask])
-        } else {
-            None
+// An empty buffer with no capacity, which keeps nothing that is added.
+#[cfg(feature = "alloc")]
+impl Default for HistoryRing<Vec<u8>> {
+    fn default() -> Self {
+        Self {
+            buf: Vec::new(),
+            len: 0,
+            next_running: 0,
         }
     }
+}
 
-    /// get_index
-    ///
-    /// Gets the history's starting index, or oldest byte
-    pub fn get_index(&self) -> usize {
-        self.next_running - self.len
-    }
-
-    /// get_last_index
-    ///
-    /// Gets the index of the most recent addtion.
-    pub fn get_last_index(&self) -> usize {
-        self.next_running.saturating_sub(1).max(self.next_running.saturating_sub(self.len))
-    }
-
-    /// get_len
-    ///
-    /// Gets the current length of data in the buffer.
-    pub fn get_len(&self) -> usize {
-        self.len
-    }
-
-    /// get_recent
-    ///
-    /// Returns the most recent bytes up to max_len.
-    pub fn get_recent(&self, max_len: usize) -> Vec<u8> {
-        let len = max_len.min(self.len);
-        let start = self.next_running - len;
-        let (v, _start_index) = self.get_vec_and_index(start, len);
-        v
-    }
-
-    /// get_vec
-    ///
-    /// Returns a history-filled vector without any index.
-    ///
-    /// Note: If you request the full buffer length and nothing extra,
-    /// you can tell that your data has shifted by looking at the vector's length:
-    ///
-    /// Example:
-    /// ```rust
-    /// use historybuffer::HistoryBuffer;
-    ///
-    /// let mut hb = HistoryBuffer::new(8);
-    /// hb.add("The Terminal History.".to_string().as_bytes());
-    /// let history = hb.get_vec(13, 8);
-    /// if history.len() < 8 {
-    ///     println!("...");
-    /// }
-    ///
-    /// assert_eq!(
-    ///     hb.get_vec(15, 6),
-    ///     "story.".to_string().as_bytes().to_vec()
-    /// );
-    /// ```
-    pub fn get_vec(&self, start_index: usize, max_len: usize) -> Vec<u8> {
-        let (v, _start_idx) = self.get_vec_and_index(start_index, max_len);
-        v
-    }
-
-    /// get_vec_and_index
-    ///
-    /// Returns a history vector along with the starting index.
-    ///
-    /// Note: The function may return less data than requested if the data has been overwritten,
-    /// or the buffer has been cleared or shortened.
-    ///
-    /// Example:
-    /// ```rust
-    /// use historybuffer::HistoryBuffer;
-    ///
-    /// fn main() {
-    ///     let mut hb = HistoryBuffer::new(6); // Create an 8-element buffer (next power of 2).
-    ///     hb.add("The Terminal History.".to_string().as_bytes());
-    ///
-    ///     assert_eq!(
-    ///         hb.get_vec_and_index(0, 100000),
-    ///         ("History.".to_string().as_bytes().to_vec(), 13usize)
-    ///     );
-    ///
-    ///     // Is the same as:
-    ///     assert_eq!(
-    ///         hb.get_vec_and_index(13, 8),
-    ///         ("History.".to_string().as_bytes().to_vec(), 13usize)
-    ///     );
-    ///
-    ///     // But it changes if more text is added:
-    ///
-    ///     hb.add(" and".to_string().as_bytes());
-    ///
-    ///     assert_eq!(
-    ///         hb.get_vec_and_index(0, 100000),
-    ///         ("ory. and".to_string().as_bytes().to_vec(), 17usize)
-    ///     );
-    ///
-    ///     assert_eq!(
-    ///         hb.get_vec_and_index(13, 8),
-    ///         ("ory.".to_string().as_bytes().to_vec(), 17usize)
-    ///     );
-    /// }
-    /// ```
-    pub fn get_vec_and_index(&self, start_index: usize, max_len: usize) -> (Vec<u8>, usize) {
-        let buf_size = self.buf.len();
-
-        let out = (start_index + max_len).min(self.next_running);
-        let inn = (start_index.max(self.next_running - self.len)).min(out);
-        let inndx = inn & self.mask;
-        let outdx = out & self.mask;
-        let num = out.saturating_sub(inn);
-        let mut v = Vec::with_capacity(num); // NOTE: Vec's need to already have FILLED vectors for .copy_from_slice to work
-        v.resize(num, 0);
-        if num == 0 {
-            return (v, 0);
-        }
-
-        if outdx > inndx {
-            v[0..num].copy_from_slice(&self.buf[inndx..outdx]);
-        } else {
-            v[0..(buf_size - inndx)].copy_from_slice(&self.buf[inndx..buf_size]);
-            v[(buf_size - inndx)..num].copy_from_slice(&self.buf[0..outdx]);
-        }
-        (v, inn)
+// Bytes written are added to the history; writes never fail.
+#[cfg(feature = "std")]
+impl std::io::Write for HistoryBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.add(buf);
+        Ok(buf.len())
     }
 
-    /// last_byte
-    ///
-    /// Returns the most recent byte added to the buffer.
-    pub fn last_byte(&self) -> Option<u8> {
-        if self.len > 0 {
-            let idx = (self.next_running + self.mask) & self.mask; // Go back by going forward mask bytes (mask = buf.len() - 1)
-            Some(self.buf[idx])
-        } else {
-            None
-        }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
     }
 }
 
+#[cfg(feature = "alloc")]
 fn next_power_of_two(n: usize) -> usize {
     let mut power = 1;
     while power < n && power < (1 << 30) {
@@ -278,12 +278,13 @@ fn next_power_of_two(n: usize) -> usize {
     power
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "alloc"))]
 mod tests {
 
     use super::*;
 
     #[test]
+    #[allow(clippy::type_complexity)]
     fn test_add_with_vectors() {
         let test_vectors: [(
             &str,
@@ -438,7 +439,7 @@ mod tests {
             v2,
             v3,
             v4,
-        ) in test_vectorsget
+        ) in test_vectors
         {
             println!("\n\n\t\t\t{}:  Adding: {:#?}", test_name, input);
             tbuf.add(input);
@@ -480,6 +481,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::type_complexity)]
     fn test_after_clear() {
         let test_vectors: [(
             &str,
@@ -590,7 +592,7 @@ mod tests {
 
         let mut tbuf = HistoryBuffer::new(5);
         for i in 0..9 {
-            let vecu8 = std::iter::repeat(b'X').take(i).collect::<Vec<u8>>();
+            let vecu8 = core::iter::repeat_n(b'X', i).collect::<Vec<u8>>();
             tbuf.add(&vecu8);
             tbuf.clear();
             let offset = tbuf.get_index();
@@ -668,6 +670,8 @@ mod tests {
         );
         print!("E:");
         assert_eq!(tbuf.get_recent(4), "ory.".to_string().as_bytes());
+        print!("F:");
+        assert_eq!(tbuf.get_recent_slice(&mut [0; 4]), "ory.".as_bytes());
         print!("H:");
         assert_eq!(tbuf.get_index(), 13);
         print!("I:");
@@ -691,4 +695,53 @@ mod tests {
             ("ory.".to_string().as_bytes().to_vec(), 17usize)
         );
     }
+
+    #[test]
+    fn test_logical_equality() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+
+        let hash = |hb: &HistoryBuffer| {
+            let mut state = DefaultHasher::new();
+            hb.hash(&mut state);
+            state.finish()
+        };
+        let mut a = HistoryBuffer::new(8);
+        a.add(b"0123456789");
+        let mut b = HistoryBuffer::new(128);
+        b.add(b"01");
+        b.add(b"23456789");
+        b.clear_at(2);
+
+        println!("        same history, different wrap point:");
+        assert_eq!(a.as_slices(), (&b"234567"[..], &b"89"[..]));
+        assert_eq!(b.as_slices(), (&b"23456789"[..], &b""[..]));
+        assert_eq!(a, b);
+        assert_eq!(hash(&a), hash(&b));
+
+        println!("        clone:");
+        let snapshot = a.clone();
+        a.add(b"x");
+        assert_ne!(a, snapshot);
+        assert_eq!(snapshot.get_vec(0, 100), b"23456789");
+
+        println!("        same bytes at other indices:");
+        let mut c = HistoryBuffer::new(8);
+        c.add(b"23456789");
+        assert_ne!(c, b);
+        assert_eq!(HistoryBuffer::new(4), HistoryBuffer::default());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_io_write() {
+        use std::io::Write;
+
+        let mut hb = HistoryBuffer::new(8);
+        let what = "History";
+        write!(hb, "The Terminal {what}.").unwrap();
+        hb.flush().unwrap();
+        assert_eq!(hb.get_vec(0, 100), b"History.");
+        assert_eq!(hb.get_last_index(), 20);
+    }
 }
@@ -0,0 +1,404 @@
+//! LinkDetector
+//!
+//! Finds clickable things in terminal output: URLs, absolute and relative
+//! file paths, compiler-style `path:line:col` references and OSC 8
+//! hyperlinks. Ranges are absolute raw indices, so escape sequences inside a
+//! link (such as color changes) are covered by its range.
+//!
+//! Paths must start with `/`, `~/`, `./` or `../`, or contain a slash and
+//! end in a file extension, so prose such as `and/or` is not matched.
+//!
+//! Lines are scanned once their newline arrives; `update` only parses bytes
+//! added since the last call. `HistoryBuffer::find_links` does a one-off scan
+//! of the lines overlapping its range, including an unfinished last line.
+//!
+//! ```rust
+//! use historybuffer::{HistoryBuffer, LinkKind};
+//!
+//! let mut hb = HistoryBuffer::new(128);
+//! hb.add(b"see https://example.com/docs.\n");
+//! hb.add(b"  --> src/main.rs:12:5\n");
+//!
+//! let links = hb.find_links(0, 100);
+//! assert_eq!(links[0].target, "https://example.com/docs");
+//! assert_eq!(links[0].range, 4..28);
+//! assert_eq!(links[1].target, "src/main.rs");
+//! assert_eq!(links[1].kind, LinkKind::FileRef { line: 12, column: Some(5) });
+//! ```
+
+use alloc::collections::VecDeque;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::HistoryBuffer;
+use crate::ansi::{self, Event, Parser};
+use crate::cooked::{line_end, line_start};
+
+/// What a [`Link`] points at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LinkKind {
+    /// `scheme://...`
+    Url,
+    /// An absolute or relative file path.
+    Path,
+    /// A `path:line` or `path:line:col` reference.
+    FileRef { line: usize, column: Option<usize> },
+    /// An OSC 8 hyperlink; the target is its URI.
+    Hyperlink,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Link {
+    /// Raw bytes of the link text.
+    pub range: Range<usize>,
+    /// URL, path or hyperlink URI.
+    pub target: String,
+    pub kind: LinkKind,
+}
+
+#[derive(Debug, Clone)]
+struct OpenHyperlink {
+    uri: String,
+    start: Option<usize>,
+    end: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LinkDetector {
+    parser: Parser,
+    scanned: usize,
+    line: Vec<(usize, u8, bool)>, // Raw index, byte, inside a hyperlink
+    hyperlink: Option<OpenHyperlink>,
+    links: VecDeque<Link>,
+}
+
+impl LinkDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// update
+    ///
+    /// Scans bytes added since the last update and forgets links whose
+    /// first byte has been evicted.
+    pub fn update(&mut self, hb: &HistoryBuffer) {
+        if self.scanned < hb.get_index() {
+            self.parser = Parser::new();
+            self.scanned = hb.get_index();
+            self.line.clear();
+            self.hyperlink = None;
+        }
+        self.scan_to(hb, hb.next_running);
+        self.evict(hb);
+    }
+
+    // Parses from scanned up to end_index.
+    fn scan_to(&mut self, hb: &HistoryBuffer, end_index: usize) {
+        let (data, inn) = hb.get_vec_and_index(self.scanned, end_index - self.scanned);
+        for (i, &byte) in data.iter().enumerate() {
+            let Some((range, event)) = self.parser.feed(inn + i, byte) else {
+                continue;
+            };
+            match event {
+                Event::Print(b) => {
+                    let linked = match &mut self.hyperlink {
+                        Some(open) => {
+                            open.start.get_or_insert(range.start);
+                            open.end = range.end;
+                            true
+                        }
+                        None => false,
+                    };
+                    self.line.push((range.start, b, linked));
+                }
+                Event::Control(b'\n') => {
+                    self.scan_line();
+                    self.line.clear();
+                }
+                Event::Control(_) => self.line.push((range.start, b' ', false)),
+                Event::Osc(payload) => self.osc(&payload),
+                _ => {}
+            }
+        }
+        self.scanned = end_index;
+    }
+
+    /// links
+    ///
+    /// Gets the links found so far, oldest first.
+    pub fn links(&self) -> impl DoubleEndedIterator<Item = &Link> + '_ {
+        self.links.iter()
+    }
+
+    /// links_in
+    ///
+    /// Gets the links overlapping an index range.
+    pub fn links_in(&self, range: Range<usize>) -> impl Iterator<Item = &Link> + '_ {
+        self.links
+            .iter()
+            .take_while(move |link| link.range.start < range.end)
+            .filter(move |link| link.range.end > range.start)
+    }
+
+    // OSC 8 ; params ; URI starts a hyperlink, an empty URI ends it.
+    fn osc(&mut self, payload: &[u8]) {
+        let Some(rest) = payload.strip_prefix(b"8;") else {
+            return;
+        };
+        let uri = rest.splitn(2, |&b| b == b';').nth(1).unwrap_or_default();
+        self.close_hyperlink();
+        if !uri.is_empty() {
+            self.hyperlink = Some(OpenHyperlink {
+                uri: String::from_utf8_lossy(uri).into_owned(),
+                start: None,
+                end: 0,
+            });
+        }
+    }
+
+    fn close_hyperlink(&mut self) {
+        if let Some(open) = self.hyperlink.take()
+            && let Some(start) = open.start
+        {
+            self.insert(Link {
+                range: start..open.end,
+                target: open.uri,
+                kind: LinkKind::Hyperlink,
+            });
+        }
+    }
+
+    // Scans the unfinished last line and ends an open hyperlink.
+    fn flush(&mut self) {
+        self.scan_line();
+        self.line.clear();
+        self.close_hyperlink();
+    }
+
+    fn scan_line(&mut self) {
+        let line = core::mem::take(&mut self.line);
+        let mut i = 0;
+        while i < line.len() {
+            if is_separator(line[i].1) {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < line.len() && !is_separator(line[i].1) {
+                i += 1;
+            }
+            let token = &line[start..i];
+            if token.iter().any(|&(_, _, linked)| linked) {
+                continue;
+            }
+            let bytes: Vec<u8> = token.iter().map(|&(_, b, _)| b).collect();
+            let (a, b) = trim_token(&bytes);
+            if let Some((target, kind)) = classify(&bytes[a..b]) {
+                self.insert(Link {
+                    range: token[a].0..token[b - 1].0 + 1,
+                    target,
+                    kind,
+                });
+            }
+        }
+        self.line = line;
+    }
+
+    // Links are kept sorted by start; hyperlinks finish after the lines they cover.
+    fn insert(&mut self, link: Link) {
+        let at = self
+            .links
+            .partition_point(|other| other.range.start <= link.range.start);
+        self.links.insert(at, link);
+    }
+
+    fn evict(&mut self, hb: &HistoryBuffer) {
+        let oldest = hb.get_index();
+        while self
+            .links
+            .front()
+            .is_some_and(|link| link.range.start < oldest)
+        {
+            self.links.pop_front();
+        }
+        self.line.retain(|&(index, _, _)| index >= oldest);
+    }
+}
+
+impl HistoryBuffer {
+    /// find_links
+    ///
+    /// Returns the links overlapping a range of history, including any on the
+    /// unfinished last line.
+    ///
+    /// Note: Only the lines overlapping the range are parsed, so a hyperlink
+    /// opened on an earlier line is not found.
+    pub fn find_links(&self, start_index: usize, max_len: usize) -> Vec<Link> {
+        let end_index = start_index.saturating_add(max_len);
+        let from = ansi::sync_point(self, line_start(self, start_index));
+        let mut detector = LinkDetector {
+            scanned: from,
+            ..Default::default()
+        };
+        detector.scan_to(self, line_end(self, end_index.saturating_sub(1).max(from)));
+        detector.flush();
+        detector.links_in(start_index..end_index).cloned().collect()
+    }
+}
+
+fn is_separator(b: u8) -> bool {
+    b.is_ascii_whitespace() || b.is_ascii_control() || b"\"'`<>".contains(&b)
+}
+
+// Strips surrounding punctuation, keeping closing brackets that are balanced.
+fn trim_token(t: &[u8]) -> (usize, usize) {
+    let mut a = 0;
+    while a < t.len() && b"([{".contains(&t[a]) {
+        a += 1;
+    }
+    let mut b = t.len();
+    while b > a {
+        let inner = &t[a..b];
+        let unbalanced = |open, close| {
+            inner.iter().filter(|&&c| c == open).count()
+                < inner.iter().filter(|&&c| c == close).count()
+        };
+        let strip = match t[b - 1] {
+            b'.' | b',' | b';' | b':' | b'!' | b'?' => true,
+            b')' => unbalanced(b'(', b')'),
+            b']' => unbalanced(b'[', b']'),
+            b'}' => unbalanced(b'{', b'}'),
+            _ => false,
+        };
+        if !strip {
+            break;
+        }
+        b -= 1;
+    }
+    (a, b)
+}
+
+fn classify(t: &[u8]) -> Option<(String, LinkKind)> {
+    if t.is_empty() {
+        return None;
+    }
+    let text = String::from_utf8_lossy(t).into_owned();
+    if let Some(p) = text.find("://") {
+        let scheme = &text[..p];
+        let valid = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c));
+        return (valid && text.len() > p + 3).then_some((text, LinkKind::Url));
+    }
+    if let Some(file_ref) = file_ref(&text) {
+        return Some(file_ref);
+    }
+    is_path(&text).then_some((text, LinkKind::Path))
+}
+
+fn file_ref(text: &str) -> Option<(String, LinkKind)> {
+    let number = |s: &str| {
+        (!s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()))
+            .then(|| s.parse::<usize>().ok())
+            .flatten()
+    };
+    let mut parts = text.rsplitn(3, ':');
+    let last = parts.next()?;
+    let middle = parts.next()?;
+    let (path, line, column) = match (parts.next(), number(middle), number(last)) {
+        (Some(path), Some(line), Some(column)) => (path, line, Some(column)),
+        (_, _, Some(line)) => (&text[..text.len() - last.len() - 1], line, None),
+        _ => return None,
+    };
+    (is_path(path) || has_extension(path))
+        .then(|| (path.to_string(), LinkKind::FileRef { line, column }))
+}
+
+// Unanchored text needs a slash and a file extension, so that words such as
+// `and/or` are not taken for paths.
+fn is_path(text: &str) -> bool {
+    let anchored = ["/", "~/", "./", "../"]
+        .iter()
+        .any(|prefix| text.starts_with(prefix));
+    if anchored {
+        return text.chars().any(char::is_alphabetic);
+    }
+    text.contains('/') && text.rsplit('/').next().is_some_and(has_extension)
+}
+
+fn has_extension(text: &str) -> bool {
+    match text.rsplit_once('.') {
+        Some((stem, ext)) => {
+            !stem.is_empty()
+                && (1..=8).contains(&ext.len())
+                && ext.chars().all(|c| c.is_ascii_alphanumeric())
+                && ext.chars().any(|c| c.is_ascii_alphabetic())
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_link_detector() {
+        let mut hb = HistoryBuffer::new(256);
+        let mut detector = LinkDetector::new();
+        hb.add(b"open (https://en.wikipedia.org/wiki/Rust_(language)) or ./run.sh\n");
+        hb.add(b"error at lib.rs:7, \x1b[1m/usr/lib/os-release\x1b[0m and 3.14 and/or src/main.rs a/b:3\n");
+        hb.add(b"\x1b]8;;file:///tmp/a.txt\x1b\\a.txt\x1b]8;;\x1b\\ http://partial");
+        detector.update(&hb);
+
+        let found: Vec<(&str, LinkKind)> = detector
+            .links()
+            .map(|link| (link.target.as_str(), link.kind))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    "https://en.wikipedia.org/wiki/Rust_(language)",
+                    LinkKind::Url
+                ),
+                ("./run.sh", LinkKind::Path),
+                (
+                    "lib.rs",
+                    LinkKind::FileRef {
+                        line: 7,
+                        column: None
+                    }
+                ),
+                ("/usr/lib/os-release", LinkKind::Path),
+                ("src/main.rs", LinkKind::Path),
+                ("file:///tmp/a.txt", LinkKind::Hyperlink),
+            ]
+        );
+        let links: Vec<&Link> = detector.links().collect();
+        assert_eq!(links[3].range, 88..107);
+        assert_eq!(
+            hb.get_vec(links[5].range.start, links[5].range.len()),
+            b"a.txt"
+        );
+
+        println!("        find_links includes the unfinished line:");
+        let links = hb.find_links(links[5].range.end, 100);
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].target, "http://partial");
+
+        println!("        find_links parses only the lines in range:");
+        let links = hb.find_links(88, 19);
+        assert_eq!(links.len(), 1);
+        assert_eq!(links[0].range, 88..107);
+        assert_eq!(links[0].target, "/usr/lib/os-release");
+
+        println!("        eviction:");
+        hb.add(&[b'.'; 122]);
+        detector.update(&hb);
+        assert_eq!(detector.links().next().unwrap().target, "lib.rs");
+    }
+}
@@ -0,0 +1,168 @@
+//! HistoryLog
+//!
+//! A ring of variable-length messages stored in a [`RecordBuffer`]. Each `push`
+//! encodes the message into a single frame: a 4 byte little endian payload
+//! length followed by the payload. Frames are evicted whole, so a message whose
+//! head has been overwritten is never decoded.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+use crate::RecordBuffer;
+
+const HEADER_LEN: usize = 4;
+
+/// A message that can be stored in a [`HistoryLog`].
+pub trait LogEntry: Sized {
+    /// Appends the encoded message to out.
+    fn encode(&self, out: &mut Vec<u8>);
+
+    /// Decodes a message previously written by `encode`.
+    fn decode(bytes: &[u8]) -> Option<Self>;
+}
+
+impl LogEntry for Vec<u8> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self);
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        Some(bytes.to_vec())
+    }
+}
+
+impl LogEntry for String {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self.as_bytes());
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        String::from_utf8(bytes.to_vec()).ok()
+    }
+}
+
+#[derive(Debug)]
+pub struct HistoryLog<T> {
+    rb: RecordBuffer,
+    frame: Vec<u8>,
+    _entry: PhantomData<T>,
+}
+
+impl<T: LogEntry> HistoryLog<T> {
+    /// Creates a log whose frames share a byte ring of at least min_buf_size.
+    pub fn new(min_buf_size: usize) -> Self {
+        Self {
+            rb: RecordBuffer::new(min_buf_size),
+            frame: Vec::new(),
+            _entry: PhantomData,
+        }
+    }
+
+    /// push
+    ///
+    /// Encodes entry into a frame and adds it, evicting the oldest frames as
+    /// needed. Returns the entry's sequence number, or None if the frame is
+    /// larger than the whole ring.
+    pub fn push(&mut self, entry: &T) -> Option<usize> {
+        self.frame.clear();
+        self.frame.extend_from_slice(&[0; HEADER_LEN]);
+        entry.encode(&mut self.frame);
+        let payload_len = u32::try_from(self.frame.len() - HEADER_LEN).ok()?;
+        if self.frame.len() > self.rb.history().buf.len() {
+            return None;
+        }
+        self.frame[..HEADER_LEN].copy_from_slice(&payload_len.to_le_bytes());
+        self.rb.add(&self.frame)
+    }
+
+    /// get
+    ///
+    /// Decodes the entry with sequence number seq if its whole frame is still
+    /// in memory.
+    pub fn get(&self, seq: usize) -> Option<T> {
+        let record = self.rb.get_record(seq)?;
+        if record.truncated || record.data.len() < HEADER_LEN {
+            return None;
+        }
+        let (header, payload) = record.data.split_at(HEADER_LEN);
+        let payload_len = u32::from_le_bytes(header.try_into().ok()?) as usize;
+        if payload_len != payload.len() {
+            return None;
+        }
+        T::decode(payload)
+    }
+
+    /// iter
+    ///
+    /// Iterates the complete entries, oldest first.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = T> + '_ {
+        (self.get_first_seq()..self.get_next_seq()).filter_map(|seq| self.get(seq))
+    }
+
+    /// get_first_seq
+    ///
+    /// Gets the sequence number of the oldest complete entry.
+    pub fn get_first_seq(&self) -> usize {
+        let first = self.rb.get_first_seq();
+        match self.rb.get_record(first) {
+            Some(record) if record.truncated => first + 1,
+            _ => first,
+        }
+    }
+
+    /// get_next_seq
+    ///
+    /// Gets the sequence number the next `push` will be given.
+    pub fn get_next_seq(&self) -> usize {
+        self.rb.get_next_seq()
+    }
+
+    /// len
+    ///
+    /// Gets the number of complete entries in the log.
+    pub fn len(&self) -> usize {
+        self.get_next_seq() - self.get_first_seq()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// clear the log
+    pub fn clear(&mut self) {
+        self.rb.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_history_log() {
+        let mut log: HistoryLog<String> = HistoryLog::new(32);
+        assert_eq!(log.push(&"first".to_string()), Some(0));
+        assert_eq!(log.push(&"second".to_string()), Some(1));
+        assert_eq!(log.push(&"third".to_string()), Some(2));
+        assert_eq!(log.len(), 3);
+        assert_eq!(log.get(1), Some("second".to_string()));
+
+        println!("        frame 0 half overwritten:");
+        assert_eq!(log.push(&"fourth".to_string()), Some(3));
+        assert_eq!(log.get(0), None);
+        assert_eq!(log.get_first_seq(), 1);
+        assert_eq!(
+            log.iter().rev().collect::<Vec<String>>(),
+            vec!["fourth", "third", "second"]
+        );
+
+        println!("        oversized frame:");
+        assert_eq!(log.push(&"x".repeat(29)), None);
+        assert_eq!(log.len(), 3);
+
+        log.clear();
+        assert!(log.is_empty());
+    }
+}
@@ -0,0 +1,205 @@
+//! MarkedHistory
+//!
+//! A [`HistoryBuffer`] with bookmarks. A marker is attached to an absolute
+//! index and is removed automatically once `add` overwrites that byte.
+//! Markers may optionally be given a name so they can be looked up later.
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::{String, ToString};
+use core::fmt;
+use core::ops::Range;
+
+use crate::HistoryBuffer;
+
+/// Handle to a marker in a [`MarkedHistory`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Marker(usize);
+
+/// The marked byte has been overwritten, or the marker was removed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Evicted;
+
+impl fmt::Display for Evicted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("position is no longer in the history")
+    }
+}
+
+impl core::error::Error for Evicted {}
+
+#[derive(Debug)]
+struct MarkerEntry {
+    index: usize,
+    name: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct MarkedHistory {
+    hb: HistoryBuffer,
+    by_handle: BTreeMap<Marker, MarkerEntry>,
+    by_index: BTreeSet<(usize, Marker)>,
+    next_handle: usize,
+}
+
+impl MarkedHistory {
+    pub fn new(min_buf_size: usize) -> Self {
+        Self {
+            hb: HistoryBuffer::new(min_buf_size),
+            ..Default::default()
+        }
+    }
+
+    /// add
+    ///
+    /// Adds data to the history, dropping markers whose bytes were overwritten.
+    pub fn add(&mut self, data: &[u8]) {
+        self.hb.add(data);
+        self.evict();
+    }
+
+    /// clear the buffer and every marker
+    pub fn clear(&mut self) {
+        self.hb.clear();
+        self.evict();
+    }
+
+    /// clear_at the buffer at a specific point, dropping markers before it
+    pub fn clear_at(&mut self, new_start_index: usize) {
+        self.hb.clear_at(new_start_index);
+        self.evict();
+    }
+
+    /// history
+    ///
+    /// Gives read access to the underlying byte history.
+    pub fn history(&self) -> &HistoryBuffer {
+        &self.hb
+    }
+
+    /// mark
+    ///
+    /// Attaches a marker to index. The index may be in the future, e.g. the
+    /// index the next `add` will start at.
+    pub fn mark(&mut self, index: usize) -> Marker {
+        self.insert(index, None)
+    }
+
+    /// mark_here
+    ///
+    /// Attaches a marker to the index the next `add` will start at.
+    pub fn mark_here(&mut self) -> Marker {
+        self.mark(self.hb.next_running)
+    }
+
+    /// mark_named
+    ///
+    /// Attaches a named marker to index, replacing any marker with the same name.
+    pub fn mark_named(&mut self, name: &str, index: usize) -> Marker {
+        if let Some(old) = self.find_marker(name) {
+            self.remove_marker(old);
+        }
+        self.insert(index, Some(name.to_string()))
+    }
+
+    /// find_marker
+    ///
+    /// Looks up a live marker by name.
+    pub fn find_marker(&self, name: &str) -> Option<Marker> {
+        self.by_handle
+            .iter()
+            .find(|(_, entry)| entry.name.as_deref() == Some(name))
+            .map(|(handle, _)| *handle)
+    }
+
+    /// marker_name
+    ///
+    /// Gets the name of a live marker, if it was given one.
+    pub fn marker_name(&self, handle: Marker) -> Option<&str> {
+        self.by_handle.get(&handle)?.name.as_deref()
+    }
+
+    /// marker_position
+    ///
+    /// Gets the absolute index of a marker, or Evicted if the marked byte has
+    /// been overwritten or the marker was removed.
+    pub fn marker_position(&self, handle: Marker) -> Result<usize, Evicted> {
+        self.by_handle
+            .get(&handle)
+            .map(|entry| entry.index)
+            .ok_or(Evicted)
+    }
+
+    /// markers_in
+    ///
+    /// Iterates the markers within range in index order, as (index, marker).
+    pub fn markers_in(&self, range: Range<usize>) -> impl Iterator<Item = (usize, Marker)> + '_ {
+        self.by_index
+            .range((range.start, Marker(0))..)
+            .take_while(move |(index, _)| *index < range.end)
+            .copied()
+    }
+
+    /// remove_marker
+    ///
+    /// Returns false if the marker was already gone.
+    pub fn remove_marker(&mut self, handle: Marker) -> bool {
+        match self.by_handle.remove(&handle) {
+            Some(entry) => self.by_index.remove(&(entry.index, handle)),
+            None => false,
+        }
+    }
+
+    fn insert(&mut self, index: usize, name: Option<String>) -> Marker {
+        let handle = Marker(self.next_handle);
+        self.next_handle += 1;
+        if index >= self.hb.get_index() {
+            self.by_handle.insert(handle, MarkerEntry { index, name });
+            self.by_index.insert((index, handle));
+        }
+        handle
+    }
+
+    // Drop markers on bytes that are no longer in memory.
+    fn evict(&mut self) {
+        let live = self.by_index.split_off(&(self.hb.get_index(), Marker(0)));
+        for (_, handle) in core::mem::replace(&mut self.by_index, live) {
+            self.by_handle.remove(&handle);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_markers() {
+        let mut mh = MarkedHistory::new(8);
+        mh.add(b"test 16\n");
+        let test17 = mh.mark_here();
+        mh.add(b"test");
+        let named = mh.mark_named("space", 12);
+        mh.add(b" 17\n");
+
+        assert_eq!(mh.marker_position(test17), Ok(8));
+        assert_eq!(mh.find_marker("space"), Some(named));
+        assert_eq!(mh.marker_name(named), Some("space"));
+        assert_eq!(
+            mh.markers_in(0..16).collect::<Vec<_>>(),
+            vec![(8, test17), (12, named)]
+        );
+        assert_eq!(mh.markers_in(9..12).count(), 0);
+
+        println!("        evicted by add:");
+        mh.add(b"x");
+        assert_eq!(mh.marker_position(test17), Err(Evicted));
+        assert_eq!(mh.marker_position(named), Ok(12));
+
+        println!("        evicted by clear_at:");
+        mh.clear_at(13);
+        assert_eq!(mh.find_marker("space"), None);
+        let stale = mh.mark(2);
+        assert_eq!(mh.marker_position(stale), Err(Evicted));
+    }
+}
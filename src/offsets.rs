@@ -0,0 +1,145 @@
+//! OffsetMap
+//!
+//! Maps positions in a derived view of the history (for example the ANSI
+//! stripped text) back to absolute raw indices, and raw indices to view
+//! positions. View positions count up from 0 like raw indices do and are
+//! never reused, so a position stays valid until its raw byte is evicted.
+//!
+//! Mappings are stored as runs of consecutive bytes, so a view that keeps
+//! long stretches of plain text costs one entry per stretch.
+
+use alloc::collections::VecDeque;
+use core::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct MapRun {
+    filtered: usize,
+    raw: usize,
+    len: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct OffsetMap {
+    runs: VecDeque<MapRun>,
+    next_filtered: usize,
+}
+
+impl OffsetMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// push
+    ///
+    /// Records that the next view position comes from raw_index, and returns
+    /// that position. Raw indices must be pushed in increasing order.
+    pub fn push(&mut self, raw_index: usize) -> usize {
+        let filtered = self.next_filtered;
+        self.next_filtered += 1;
+        match self.runs.back_mut() {
+            Some(run) if run.raw + run.len == raw_index => run.len += 1,
+            _ => self.runs.push_back(MapRun {
+                filtered,
+                raw: raw_index,
+                len: 1,
+            }),
+        }
+        filtered
+    }
+
+    /// evict_before
+    ///
+    /// Forgets every mapping whose raw index is below raw_index.
+    pub fn evict_before(&mut self, raw_index: usize) {
+        while let Some(run) = self.runs.front_mut() {
+            if run.raw + run.len <= raw_index {
+                self.runs.pop_front();
+            } else {
+                let cut = raw_index.saturating_sub(run.raw);
+                run.raw += cut;
+                run.filtered += cut;
+                run.len -= cut;
+                break;
+            }
+        }
+    }
+
+    /// get_filtered_range
+    ///
+    /// Gets the view positions that still have a mapping.
+    pub fn get_filtered_range(&self) -> Range<usize> {
+        let start = self
+            .runs
+            .front()
+            .map_or(self.next_filtered, |run| run.filtered);
+        start..self.next_filtered
+    }
+
+    /// to_raw
+    ///
+    /// Translates a view position to its absolute raw index.
+    pub fn to_raw(&self, filtered: usize) -> Option<usize> {
+        let pos = self.runs.partition_point(|run| run.filtered <= filtered);
+        let run = self.runs.get(pos.checked_sub(1)?)?;
+        let offset = filtered - run.filtered;
+        (offset < run.len).then_some(run.raw + offset)
+    }
+
+    /// to_filtered
+    ///
+    /// Translates a raw index to a view position. Like `binary_search`, a raw
+    /// byte the view left out (e.g. part of an escape sequence) gives
+    /// `Err` with the position of the next byte the view kept.
+    pub fn to_filtered(&self, raw: usize) -> Result<usize, usize> {
+        let pos = self.runs.partition_point(|run| run.raw <= raw);
+        let Some(run) = pos.checked_sub(1).and_then(|i| self.runs.get(i)) else {
+            return Err(self.get_filtered_range().start);
+        };
+        let offset = raw - run.raw;
+        if offset < run.len {
+            Ok(run.filtered + offset)
+        } else {
+            Err(run.filtered + run.len)
+        }
+    }
+
+    /// range_to_raw
+    ///
+    /// Translates a range of view positions to the raw range spanning them,
+    /// including any bytes the view left out in between.
+    pub fn range_to_raw(&self, filtered: Range<usize>) -> Option<Range<usize>> {
+        if filtered.is_empty() {
+            return None;
+        }
+        Some(self.to_raw(filtered.start)?..self.to_raw(filtered.end - 1)? + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_offset_map() {
+        let mut map = OffsetMap::new();
+        // Raw "ab\x1b[mcd": view "abcd" from raw 0, 1, 5, 6
+        for raw in [0, 1, 5, 6] {
+            map.push(raw);
+        }
+        assert_eq!(map.to_raw(2), Some(5));
+        assert_eq!(map.to_raw(4), None);
+        assert_eq!(map.to_filtered(6), Ok(3));
+        assert_eq!(map.to_filtered(3), Err(2));
+        assert_eq!(map.range_to_raw(1..3), Some(1..6));
+
+        println!("        evicted:");
+        map.evict_before(1);
+        assert_eq!(map.get_filtered_range(), 1..4);
+        assert_eq!(map.to_raw(0), None);
+        assert_eq!(map.to_raw(1), Some(1));
+        map.evict_before(6);
+        assert_eq!(map.get_filtered_range(), 3..4);
+        assert_eq!(map.to_filtered(2), Err(3));
+    }
+}
@@ -0,0 +1,198 @@
+//! RecordBuffer
+//!
+//! A [`HistoryBuffer`] that remembers where each `add` call started, so packets
+//! and log records can be read back one at a time instead of as one merged
+//! byte stream.
+//!
+//! Every non-empty `add` becomes a record with a sequence number. Records are
+//! dropped once all of their bytes have been overwritten. A record whose head
+//! has been overwritten but whose tail is still in memory is returned with
+//! `truncated` set, so the caller never mistakes a partial record for a whole one.
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::HistoryBuffer;
+
+#[derive(Debug, Default)]
+pub struct RecordBuffer {
+    hb: HistoryBuffer,
+    starts: VecDeque<usize>,
+    first_seq: usize,
+}
+
+/// A single `add` call read back from a [`RecordBuffer`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    /// Sequence number of the `add` call, starting at 0.
+    pub seq: usize,
+    /// Absolute byte range the record occupied when it was added.
+    pub range: Range<usize>,
+    /// The bytes still in memory. Shorter than `range` when truncated.
+    pub data: Vec<u8>,
+    /// Set when the head of the record has already been overwritten.
+    pub truncated: bool,
+}
+
+impl RecordBuffer {
+    pub fn new(min_buf_size: usize) -> Self {
+        Self {
+            hb: HistoryBuffer::new(min_buf_size),
+            ..Default::default()
+        }
+    }
+
+    /// add
+    ///
+    /// Adds data as a new record and returns its sequence number.
+    /// Empty slices are ignored, the same as `HistoryBuffer::add`.
+    pub fn add(&mut self, data: &[u8]) -> Option<usize> {
+        if data.is_empty() {
+            return None;
+        }
+        self.starts.push_back(self.hb.next_running);
+        self.hb.add(data);
+        self.evict();
+        Some(self.first_seq + self.starts.len() - 1)
+    }
+
+    /// clear the buffer and drop every record
+    pub fn clear(&mut self) {
+        self.hb.clear();
+        self.evict();
+    }
+
+    /// clear_at the buffer at a specific point
+    ///
+    /// Records ending before new_start_index are dropped, a record spanning it
+    /// becomes truncated.
+    pub fn clear_at(&mut self, new_start_index: usize) {
+        self.hb.clear_at(new_start_index);
+        self.evict();
+    }
+
+    /// history
+    ///
+    /// Gives read access to the underlying byte history.
+    pub fn history(&self) -> &HistoryBuffer {
+        &self.hb
+    }
+
+    /// get_first_seq
+    ///
+    /// Gets the sequence number of the oldest record still (partly) in memory.
+    pub fn get_first_seq(&self) -> usize {
+        self.first_seq
+    }
+
+    /// get_next_seq
+    ///
+    /// Gets the sequence number the next `add` will be given.
+    pub fn get_next_seq(&self) -> usize {
+        self.first_seq + self.starts.len()
+    }
+
+    /// get_record_range
+    ///
+    /// Gets the absolute byte range a record occupied when it was added.
+    pub fn get_record_range(&self, seq: usize) -> Option<Range<usize>> {
+        let pos = seq.checked_sub(self.first_seq)?;
+        let start = *self.starts.get(pos)?;
+        let end = self
+            .starts
+            .get(pos + 1)
+            .copied()
+            .unwrap_or(self.hb.next_running);
+        Some(start..end)
+    }
+
+    /// get_record
+    ///
+    /// Gets a record by sequence number, or None if it has been fully overwritten.
+    pub fn get_record(&self, seq: usize) -> Option<Record> {
+        let range = self.get_record_range(seq)?;
+        let (data, start) = self.hb.get_vec_and_index(range.start, range.len());
+        Some(Record {
+            seq,
+            truncated: start > range.start,
+            range,
+            data,
+        })
+    }
+
+    /// record_at
+    ///
+    /// Gets the sequence number of the record containing the byte at index.
+    pub fn record_at(&self, index: usize) -> Option<usize> {
+        self.hb.get(index)?;
+        let pos = self.starts.partition_point(|&start| start <= index);
+        Some(self.first_seq + pos.checked_sub(1)?)
+    }
+
+    /// records_rev
+    ///
+    /// Iterates the live records, newest first.
+    pub fn records_rev(&self) -> impl Iterator<Item = Record> + '_ {
+        (self.first_seq..self.get_next_seq())
+            .rev()
+            .filter_map(|seq| self.get_record(seq))
+    }
+
+    // Drop records whose last byte is no longer in memory.
+    fn evict(&mut self) {
+        let oldest = self.hb.get_index();
+        while !self.starts.is_empty() {
+            let end = self.starts.get(1).copied().unwrap_or(self.hb.next_running);
+            if end > oldest {
+                break;
+            }
+            self.starts.pop_front();
+            self.first_seq += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_records() {
+        let mut rb = RecordBuffer::new(8);
+        assert_eq!(rb.add(b"one"), Some(0));
+        assert_eq!(rb.add(b""), None);
+        assert_eq!(rb.add(b"two"), Some(1));
+        assert_eq!(rb.add(b"threes"), Some(2));
+
+        println!("        record 0 evicted:");
+        assert_eq!(rb.get_first_seq(), 1);
+        assert_eq!(rb.get_record(0), None);
+
+        println!("        record 1 truncated:");
+        assert_eq!(
+            rb.get_record(1),
+            Some(Record {
+                seq: 1,
+                range: 3..6,
+                data: b"wo".to_vec(),
+                truncated: true,
+            })
+        );
+        assert_eq!(rb.get_record_range(2), Some(6..12));
+        assert_eq!(rb.record_at(4), Some(1));
+        assert_eq!(rb.record_at(6), Some(2));
+        assert_eq!(rb.record_at(3), None);
+
+        println!("        records_rev:");
+        let seqs: Vec<usize> = rb.records_rev().map(|r| r.seq).collect();
+        assert_eq!(seqs, vec![2, 1]);
+
+        rb.clear_at(6);
+        assert_eq!(rb.get_first_seq(), 2);
+        rb.clear();
+        assert_eq!(rb.records_rev().count(), 0);
+        assert_eq!(rb.add(b"four"), Some(3));
+    }
+}
@@ -0,0 +1,331 @@
+//! Ring arithmetic
+//!
+//! The index math shared by every buffer type, whatever owns its storage.
+//! Each function takes the storage (a power of two long) and the absolute
+//! indices; callers keep `next_running` and `len` themselves.
+//!
+//! [`HistoryRing`] wraps them into one buffer type that is generic over its
+//! storage, so the allocated, fixed-size and borrowed buffers share a single
+//! API.
+
+#[cfg(feature = "alloc")]
+use alloc::{vec, vec::Vec};
+use core::hash::{Hash, Hasher};
+use core::ops::Range;
+
+/// Copies data into the ring so that it ends just before next_running, which
+/// already counts it. Only the last buf.len() bytes can survive, so only
+/// those are copied.
+pub(crate) fn write(buf: &mut [u8], next_running: usize, data: &[u8]) {
+    let data = &data[data.len().saturating_sub(buf.len())..];
+    if data.is_empty() {
+        return;
+    }
+    let inndx = (next_running - data.len()) & (buf.len() - 1);
+    let first = data.len().min(buf.len() - inndx);
+    buf[inndx..inndx + first].copy_from_slice(&data[..first]);
+    buf[..data.len() - first].copy_from_slice(&data[first..]);
+}
+
+/// The part of start_index..start_index + max_len still in the history.
+pub(crate) fn live(
+    next_running: usize,
+    len: usize,
+    start_index: usize,
+    max_len: usize,
+) -> Range<usize> {
+    let out = start_index.saturating_add(max_len).min(next_running);
+    let inn = start_index.max(next_running - len).min(out);
+    inn..out
+}
+
+/// The byte at index, if it is still in the history.
+pub(crate) fn get(buf: &[u8], next_running: usize, len: usize, index: usize) -> Option<u8> {
+    if (index >= next_running - len) && (index < next_running) {
+        Some(buf[index & (buf.len() - 1)])
+    } else {
+        None
+    }
+}
+
+/// A live range as two slices of the ring, the second one empty unless the
+/// range wraps around its end.
+pub(crate) fn slices(buf: &[u8], range: Range<usize>) -> (&[u8], &[u8]) {
+    if range.is_empty() {
+        return (&[], &[]);
+    }
+    let inndx = range.start & (buf.len() - 1);
+    let first = range.len().min(buf.len() - inndx);
+    (&buf[inndx..inndx + first], &buf[..range.len() - first])
+}
+
+/// Copies a live range into the front of out, which must be long enough.
+pub(crate) fn read(buf: &[u8], range: Range<usize>, out: &mut [u8]) {
+    let (first, second) = slices(buf, range);
+    out[..first.len()].copy_from_slice(first);
+    out[first.len()..first.len() + second.len()].copy_from_slice(second);
+}
+
+/// Copies start_index..start_index + max_len, as far as it is live, into a
+/// new vector. The index is 0 when nothing was copied.
+#[cfg(feature = "alloc")]
+pub(crate) fn vec_and_index(
+    buf: &[u8],
+    next_running: usize,
+    len: usize,
+    start_index: usize,
+    max_len: usize,
+) -> (Vec<u8>, usize) {
+    let range = live(next_running, len, start_index, max_len);
+    let mut v = vec![0; range.len()];
+    if range.is_empty() {
+        return (v, 0);
+    }
+    let start = range.start;
+    read(buf, range, &mut v);
+    (v, start)
+}
+
+/// A history ring over any storage whose length is a power of two. The
+/// storage decides how it is owned: `HistoryBuffer` allocates a `Vec`,
+/// [`StaticHistoryBuffer`] keeps an inline array and [`HistoryBufferRef`]
+/// borrows the caller's memory. All of them share the methods below.
+///
+/// [`StaticHistoryBuffer`]: crate::StaticHistoryBuffer
+/// [`HistoryBufferRef`]: crate::HistoryBufferRef
+#[derive(Clone)]
+pub struct HistoryRing<S> {
+    pub(crate) buf: S,
+    pub(crate) len: usize,
+    pub(crate) next_running: usize,
+}
+
+impl<S: AsRef<[u8]> + AsMut<[u8]>> HistoryRing<S> {
+    /// add
+    ///
+    /// This function ingests data slices and copies them to the internal buffer.
+    pub fn add(&mut self, data: &[u8]) {
+        if data.is_empty() {
+            return;
+        }
+
+        let buf = self.buf.as_mut();
+        self.next_running += data.len();
+        self.len = (self.len + data.len()).min(buf.len());
+        write(buf, self.next_running, data);
+    }
+
+    /// clear the buffer
+    ///
+    /// Clear the len - Data is still there, but access is denied.
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// clear_at the buffer at a specific point
+    ///
+    /// Set the len to erase all history before start_index.
+    /// Data is still there, but access is denied.
+    pub fn clear_at(&mut self, new_start_index: usize) {
+        self.len = self
+            .next_running
+            .saturating_sub(new_start_index)
+            .min(self.len);
+    }
+
+    /// get
+    ///
+    /// Gets the byte value at index.
+    pub fn get(&self, index: usize) -> Option<u8> {
+        get(self.buf.as_ref(), self.next_running, self.len, index)
+    }
+
+    /// get_index
+    ///
+    /// Gets the history's starting index, or oldest byte
+    pub fn get_index(&self) -> usize {
+        self.next_running - self.len
+    }
+
+    /// get_last_index
+    ///
+    /// Gets the index of the most recent addtion.
+    pub fn get_last_index(&self) -> usize {
+        self.next_running.saturating_sub(1).max(self.get_index())
+    }
+
+    /// get_len
+    ///
+    /// Gets the current length of data in the buffer.
+    pub fn get_len(&self) -> usize {
+        self.len
+    }
+
+    /// get_recent
+    ///
+    /// Returns the most recent bytes up to max_len.
+    #[cfg(feature = "alloc")]
+    pub fn get_recent(&self, max_len: usize) -> Vec<u8> {
+        let len = max_len.min(self.len);
+        self.get_vec(self.next_running - len, len)
+    }
+
+    /// get_recent_slice
+    ///
+    /// Copies the most recent bytes into out, up to its length, and returns
+    /// the filled part. Like `get_recent`, without allocating.
+    pub fn get_recent_slice<'o>(&self, out: &'o mut [u8]) -> &'o [u8] {
+        let len = out.len().min(self.len);
+        self.get_slice_and_index(self.next_running - len, out).0
+    }
+
+    /// get_vec
+    ///
+    /// Returns a history-filled vector without any index.
+    ///
+    /// Note: If you request the full buffer length and nothing extra,
+    /// you can tell that your data has shifted by looking at the vector's length:
+    ///
+    /// Example:
+    /// ```rust
+    /// use historybuffer::HistoryBuffer;
+    ///
+    /// let mut hb = HistoryBuffer::new(8);
+    /// hb.add("The Terminal History.".to_string().as_bytes());
+    /// let history = hb.get_vec(13, 8);
+    /// if history.len() < 8 {
+    ///     println!("...");
+    /// }
+    ///
+    /// assert_eq!(
+    ///     hb.get_vec(15, 6),
+    ///     "story.".to_string().as_bytes().to_vec()
+    /// );
+    /// ```
+    #[cfg(feature = "alloc")]
+    pub fn get_vec(&self, start_index: usize, max_len: usize) -> Vec<u8> {
+        let (v, _start_idx) = self.get_vec_and_index(start_index, max_len);
+        v
+    }
+
+    /// get_vec_and_index
+    ///
+    /// Returns a history vector along with the starting index.
+    ///
+    /// Note: The function may return less data than requested if the data has been overwritten,
+    /// or the buffer has been cleared or shortened.
+    ///
+    /// Example:
+    /// ```rust
+    /// use historybuffer::HistoryBuffer;
+    ///
+    /// let mut hb = HistoryBuffer::new(6); // Create an 8-element buffer (next power of 2).
+    /// hb.add("The Terminal History.".to_string().as_bytes());
+    ///
+    /// assert_eq!(
+    ///     hb.get_vec_and_index(0, 100000),
+    ///     ("History.".to_string().as_bytes().to_vec(), 13usize)
+    /// );
+    ///
+    /// // Is the same as:
+    /// assert_eq!(
+    ///     hb.get_vec_and_index(13, 8),
+    ///     ("History.".to_string().as_bytes().to_vec(), 13usize)
+    /// );
+    ///
+    /// // But it changes if more text is added:
+    ///
+    /// hb.add(" and".to_string().as_bytes());
+    ///
+    /// assert_eq!(
+    ///     hb.get_vec_and_index(0, 100000),
+    ///     ("ory. and".to_string().as_bytes().to_vec(), 17usize)
+    /// );
+    ///
+    /// assert_eq!(
+    ///     hb.get_vec_and_index(13, 8),
+    ///     ("ory.".to_string().as_bytes().to_vec(), 17usize)
+    /// );
+    /// ```
+    #[cfg(feature = "alloc")]
+    pub fn get_vec_and_index(&self, start_index: usize, max_len: usize) -> (Vec<u8>, usize) {
+        vec_and_index(
+            self.buf.as_ref(),
+            self.next_running,
+            self.len,
+            start_index,
+            max_len,
+        )
+    }
+
+    /// get_slice_and_index
+    ///
+    /// Copies history from start_index into out, up to its length, and
+    /// returns the filled part with the index of its first byte. Like
+    /// `get_vec_and_index`, it may return less than asked for, and the index
+    /// is 0 when nothing was copied.
+    pub fn get_slice_and_index<'o>(
+        &self,
+        start_index: usize,
+        out: &'o mut [u8],
+    ) -> (&'o [u8], usize) {
+        let range = live(self.next_running, self.len, start_index, out.len());
+        if range.is_empty() {
+            return (&[], 0);
+        }
+        let num = range.len();
+        let start = range.start;
+        read(self.buf.as_ref(), range, out);
+        (&out[..num], start)
+    }
+
+    /// as_slices
+    ///
+    /// Returns the live history as two slices, oldest bytes first. The second
+    /// slice is empty unless the history wraps around the end of the ring.
+    pub fn as_slices(&self) -> (&[u8], &[u8]) {
+        slices(self.buf.as_ref(), self.get_index()..self.next_running)
+    }
+
+    /// last_byte
+    ///
+    /// Returns the most recent byte added to the buffer.
+    pub fn last_byte(&self) -> Option<u8> {
+        self.get(self.next_running.wrapping_sub(1))
+    }
+}
+
+// Buffers are equal when they hold the same bytes at the same indices,
+// whatever their capacity or where the ring wraps.
+impl<S: AsRef<[u8]> + AsMut<[u8]>> PartialEq for HistoryRing<S> {
+    fn eq(&self, other: &Self) -> bool {
+        if self.get_index() != other.get_index() || self.len != other.len {
+            return false;
+        }
+        let (a1, a2) = self.as_slices();
+        let (b1, b2) = other.as_slices();
+        a1.iter().chain(a2).eq(b1.iter().chain(b2))
+    }
+}
+
+impl<S: AsRef<[u8]> + AsMut<[u8]>> Eq for HistoryRing<S> {}
+
+impl<S: AsRef<[u8]> + AsMut<[u8]>> Hash for HistoryRing<S> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.get_index().hash(state);
+        self.len.hash(state);
+        // Fixed size chunks, so the hash does not depend on where the ring wraps
+        let (first, second) = self.as_slices();
+        let mut chunk = [0u8; 64];
+        let mut n = 0;
+        for &byte in first.iter().chain(second) {
+            chunk[n] = byte;
+            n += 1;
+            if n == chunk.len() {
+                state.write(&chunk);
+                n = 0;
+            }
+        }
+        state.write(&chunk[..n]);
+    }
+}
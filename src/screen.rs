@@ -0,0 +1,582 @@
+//! Screen
+//!
+//! A small VT100 / xterm screen emulator. Feeding it the raw bytes kept in a
+//! [`HistoryBuffer`] reconstructs what the terminal showed: a grid of cells
+//! holding characters and SGR attributes.
+//!
+//! Supported: cursor movement, erase in display / line, insert and delete of
+//! lines and characters, scroll regions (DECSTBM), index / reverse index,
+//! save / restore cursor, tab stops every 8 columns, autowrap (DECAWM) and
+//! SGR colors. Everything else is parsed and ignored.
+//!
+//! Characters take the columns `char_width` gives them, like in [`WrapView`]
+//! and [`Selector`]: a wide character fills its cell and the one after it,
+//! and a combining mark is added to the character before it.
+//!
+//! [`WrapView`]: crate::WrapView
+//! [`Selector`]: crate::Selector
+//!
+//! ```rust
+//! use historybuffer::{HistoryBuffer, Screen};
+//!
+//! let mut hb = HistoryBuffer::new(64);
+//! hb.add(b"hello\r\nworld\x1b[1;2HE");
+//!
+//! let screen = Screen::replay(&hb, hb.get_last_index() + 1, 2, 10);
+//! assert_eq!(screen.row_text(0), "hEllo");
+//! assert_eq!(screen.row_text(1), "world");
+//! assert_eq!(screen.cursor(), (0, 2));
+//! ```
+
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::HistoryBuffer;
+use crate::ansi::{Attrs, Csi, Event, Parser};
+use crate::utf8::{Utf8Decoder, char_width};
+use crate::wrap::TAB_WIDTH;
+
+// Combining marks kept per cell; more are dropped.
+const MAX_COMBINING: usize = 2;
+
+/// One character cell of the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Cell {
+    pub ch: char,
+    pub attrs: Attrs,
+    /// Columns the character takes: 2 for a wide character, 0 for the cell
+    /// after one, which it covers, otherwise 1.
+    pub width: u8,
+    /// Combining marks drawn over the character.
+    pub combining: [Option<char>; MAX_COMBINING],
+}
+
+impl Cell {
+    /// chars
+    ///
+    /// Gets the character followed by its combining marks.
+    pub fn chars(&self) -> impl Iterator<Item = char> + '_ {
+        core::iter::once(self.ch).chain(self.combining.iter().flatten().copied())
+    }
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            ch: ' ',
+            attrs: Attrs::default(),
+            width: 1,
+            combining: [None; MAX_COMBINING],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Cursor {
+    row: usize,
+    col: usize,
+    attrs: Attrs,
+}
+
+#[derive(Debug, Clone)]
+pub struct Screen {
+    rows: usize,
+    cols: usize,
+    grid: Vec<Vec<Cell>>,
+    cursor: Cursor,
+    saved: Cursor,
+    pending_wrap: bool,
+    autowrap: bool,
+    scroll_top: usize,
+    scroll_bottom: usize, // Exclusive
+    parser: Parser,
+    decoder: Utf8Decoder,
+    index: usize,
+}
+
+impl Screen {
+    /// Creates a blank screen. Sizes are clamped to at least 1 x 1.
+    pub fn new(rows: usize, cols: usize) -> Self {
+        let (rows, cols) = (rows.max(1), cols.max(1));
+        Self {
+            rows,
+            cols,
+            grid: vec![vec![Cell::default(); cols]; rows],
+            cursor: Cursor::default(),
+            saved: Cursor::default(),
+            pending_wrap: false,
+            autowrap: true,
+            scroll_top: 0,
+            scroll_bottom: rows,
+            parser: Parser::new(),
+            decoder: Utf8Decoder::new(),
+            index: 0,
+        }
+    }
+
+    /// replay
+    ///
+    /// Builds the screen as it looked just before end_index, replaying the
+    /// history from its oldest byte. Bytes evicted from the history are
+    /// missing from the replay, so the result is exact only while the oldest
+    /// byte is still the start of the session or a point where the screen was
+    /// cleared.
+    pub fn replay(hb: &HistoryBuffer, end_index: usize, rows: usize, cols: usize) -> Self {
+        let mut screen = Screen::new(rows, cols);
+        let start = hb.get_index();
+        let (data, inn) = hb.get_vec_and_index(start, end_index.saturating_sub(start));
+        screen.index = inn;
+        screen.feed(&data);
+        screen
+    }
+
+    /// feed
+    ///
+    /// Interprets more terminal output.
+    pub fn feed(&mut self, data: &[u8]) {
+        for &byte in data {
+            let index = self.index;
+            self.index += 1;
+            match self.parser.feed(index, byte) {
+                Some((_, Event::Print(b))) => {
+                    let mut decoder = self.decoder;
+                    decoder.feed(b, |c| self.print(c));
+                    self.decoder = decoder;
+                }
+                Some((_, Event::Control(b))) => self.control(b),
+                Some((
+                    _,
+                    Event::Esc {
+                        intermediates,
+                        final_byte,
+                    },
+                )) if intermediates.is_empty() => self.esc(final_byte),
+                Some((_, Event::Csi(csi))) => self.csi(&csi),
+                _ => {}
+            }
+        }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// cursor
+    ///
+    /// Gets the cursor position as (row, column), counting from 0.
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.cursor.row, self.cursor.col)
+    }
+
+    /// cell
+    ///
+    /// Gets the cell at row, column, or None if it is off the screen.
+    pub fn cell(&self, row: usize, col: usize) -> Option<&Cell> {
+        self.grid.get(row)?.get(col)
+    }
+
+    /// row_cells
+    ///
+    /// Gets a whole row of cells.
+    pub fn row_cells(&self, row: usize) -> Option<&[Cell]> {
+        self.grid.get(row).map(|r| r.as_slice())
+    }
+
+    /// row_text
+    ///
+    /// Gets the characters of a row with trailing blanks removed.
+    pub fn row_text(&self, row: usize) -> String {
+        let text: String = self
+            .grid
+            .get(row)
+            .into_iter()
+            .flatten()
+            .filter(|cell| cell.width > 0)
+            .flat_map(Cell::chars)
+            .collect();
+        text.trim_end_matches(' ').to_string()
+    }
+
+    /// text
+    ///
+    /// Gets every row's text joined by newlines.
+    pub fn text(&self) -> String {
+        (0..self.rows)
+            .map(|row| self.row_text(row))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    fn print(&mut self, c: char) {
+        let width = char_width(c).min(self.cols);
+        if width == 0 {
+            self.combine(c);
+            return;
+        }
+        // A wide character with one column left goes to the next line
+        let no_room = self.cursor.col + width > self.cols;
+        if self.pending_wrap || (no_room && self.autowrap) {
+            self.pending_wrap = false;
+            self.cursor.col = 0;
+            self.linefeed();
+        }
+        let (row, col) = (self.cursor.row, self.cursor.col.min(self.cols - width));
+        // Overwriting half of a wide character blanks the other half
+        if col > 0 && self.grid[row][col].width == 0 {
+            self.grid[row][col - 1] = self.blank_cell();
+        }
+        if let Some(next) = self.grid[row].get(col + width)
+            && next.width == 0
+        {
+            self.grid[row][col + width] = self.blank_cell();
+        }
+        let cell = Cell {
+            ch: c,
+            attrs: self.cursor.attrs,
+            width: width as u8,
+            ..Cell::default()
+        };
+        self.grid[row][col] = cell;
+        if width == 2 {
+            self.grid[row][col + 1] = Cell { width: 0, ..cell };
+        }
+        if col + width < self.cols {
+            self.cursor.col = col + width;
+        } else {
+            self.cursor.col = self.cols - 1;
+            self.pending_wrap = self.autowrap;
+        }
+    }
+
+    // Adds a zero width character to the cell printed last.
+    fn combine(&mut self, c: char) {
+        let col = if self.pending_wrap {
+            Some(self.cursor.col)
+        } else {
+            self.cursor.col.checked_sub(1)
+        };
+        let Some(mut col) = col else {
+            return;
+        };
+        let row = &mut self.grid[self.cursor.row];
+        if row[col].width == 0 && col > 0 {
+            col -= 1;
+        }
+        if let Some(slot) = row[col].combining.iter_mut().find(|m| m.is_none()) {
+            *slot = Some(c);
+        }
+    }
+
+    fn control(&mut self, byte: u8) {
+        match byte {
+            0x08 => self.move_to(self.cursor.row, self.cursor.col.saturating_sub(1)),
+            b'\t' => {
+                let col = (self.cursor.col / TAB_WIDTH + 1) * TAB_WIDTH;
+                self.move_to(self.cursor.row, col.min(self.cols - 1));
+            }
+            b'\n' | 0x0b | 0x0c => {
+                self.pending_wrap = false;
+                self.linefeed();
+            }
+            b'\r' => self.move_to(self.cursor.row, 0),
+            _ => {}
+        }
+    }
+
+    fn esc(&mut self, final_byte: u8) {
+        match final_byte {
+            b'7' => self.saved = self.cursor,
+            b'8' => {
+                self.cursor = self.saved;
+                self.pending_wrap = false;
+            }
+            b'D' => self.linefeed(),
+            b'E' => {
+                self.linefeed();
+                self.move_to(self.cursor.row, 0);
+            }
+            b'M' => {
+                if self.cursor.row == self.scroll_top {
+                    self.scroll_down(1);
+                } else {
+                    self.move_to(self.cursor.row.saturating_sub(1), self.cursor.col);
+                }
+            }
+            b'c' => {
+                let index = self.index;
+                *self = Screen::new(self.rows, self.cols);
+                self.index = index;
+            }
+            _ => {}
+        }
+    }
+
+    fn csi(&mut self, csi: &Csi) {
+        if csi.private() == Some(b'?') {
+            if csi.params().contains(&7) {
+                match csi.final_byte {
+                    b'h' => self.autowrap = true,
+                    b'l' => self.autowrap = false,
+                    _ => {}
+                }
+            }
+            return;
+        }
+        if csi.private().is_some() || !csi.intermediates.is_empty() {
+            return;
+        }
+
+        let n = csi.param(0, 1) as usize;
+        let (row, col) = (self.cursor.row, self.cursor.col);
+        match csi.final_byte {
+            b'A' => self.move_to(row.saturating_sub(n).max(self.top_limit(row)), col),
+            b'B' | b'e' => self.move_to((row + n).min(self.bottom_limit(row)), col),
+            b'C' | b'a' => self.move_to(row, col + n),
+            b'D' => self.move_to(row, col.saturating_sub(n)),
+            b'E' => self.move_to((row + n).min(self.bottom_limit(row)), 0),
+            b'F' => self.move_to(row.saturating_sub(n).max(self.top_limit(row)), 0),
+            b'G' | b'`' => self.move_to(row, n - 1),
+            b'd' => self.move_to(n - 1, col),
+            b'H' | b'f' => self.move_to(n - 1, csi.param(1, 1) as usize - 1),
+            b'J' => self.erase_display(csi.param(0, 0)),
+            b'K' => self.erase_line(csi.param(0, 0)),
+            b'L' => self.insert_lines(n),
+            b'M' => self.delete_lines(n),
+            b'@' => self.insert_chars(n),
+            b'P' => self.delete_chars(n),
+            b'X' => {
+                let end = (col + n).min(self.cols);
+                self.blank(row, col..end);
+            }
+            b'S' => self.scroll_up(n),
+            b'T' => self.scroll_down(n),
+            b'r' => {
+                let top = csi.param(0, 1) as usize - 1;
+                let bottom = (csi.param(1, self.rows as u16) as usize).min(self.rows);
+                if top + 1 < bottom {
+                    self.scroll_top = top;
+                    self.scroll_bottom = bottom;
+                    self.move_to(0, 0);
+                }
+            }
+            b's' => self.saved = self.cursor,
+            b'u' => {
+                self.cursor = self.saved;
+                self.pending_wrap = false;
+            }
+            b'm' => self.cursor.attrs.apply_sgr(csi),
+            _ => {}
+        }
+    }
+
+    // Vertical movement stops at the scroll region margins when starting inside it.
+    fn top_limit(&self, row: usize) -> usize {
+        if row >= self.scroll_top {
+            self.scroll_top
+        } else {
+            0
+        }
+    }
+
+    fn bottom_limit(&self, row: usize) -> usize {
+        if row < self.scroll_bottom {
+            self.scroll_bottom - 1
+        } else {
+            self.rows - 1
+        }
+    }
+
+    fn move_to(&mut self, row: usize, col: usize) {
+        self.cursor.row = row.min(self.rows - 1);
+        self.cursor.col = col.min(self.cols - 1);
+        self.pending_wrap = false;
+    }
+
+    fn linefeed(&mut self) {
+        if self.cursor.row + 1 == self.scroll_bottom {
+            self.scroll_up(1);
+        } else if self.cursor.row + 1 < self.rows {
+            self.cursor.row += 1;
+        }
+    }
+
+    fn blank_cell(&self) -> Cell {
+        Cell {
+            attrs: Attrs {
+                bg: self.cursor.attrs.bg,
+                ..Default::default()
+            },
+            ..Cell::default()
+        }
+    }
+
+    fn blank(&mut self, row: usize, cols: core::ops::Range<usize>) {
+        let cell = self.blank_cell();
+        self.grid[row][cols].fill(cell);
+    }
+
+    fn blank_row(&self) -> Vec<Cell> {
+        vec![self.blank_cell(); self.cols]
+    }
+
+    fn scroll_up(&mut self, n: usize) {
+        let n = n.min(self.scroll_bottom - self.scroll_top);
+        let blank = self.blank_row();
+        self.grid[self.scroll_top..self.scroll_bottom].rotate_left(n);
+        for row in self.scroll_bottom - n..self.scroll_bottom {
+            self.grid[row] = blank.clone();
+        }
+    }
+
+    fn scroll_down(&mut self, n: usize) {
+        let n = n.min(self.scroll_bottom - self.scroll_top);
+        let blank = self.blank_row();
+        self.grid[self.scroll_top..self.scroll_bottom].rotate_right(n);
+        for row in self.scroll_top..self.scroll_top + n {
+            self.grid[row] = blank.clone();
+        }
+    }
+
+    fn insert_lines(&mut self, n: usize) {
+        let row = self.cursor.row;
+        if (self.scroll_top..self.scroll_bottom).contains(&row) {
+            let top = core::mem::replace(&mut self.scroll_top, row);
+            self.scroll_down(n);
+            self.scroll_top = top;
+            self.move_to(row, 0);
+        }
+    }
+
+    fn delete_lines(&mut self, n: usize) {
+        let row = self.cursor.row;
+        if (self.scroll_top..self.scroll_bottom).contains(&row) {
+            let top = core::mem::replace(&mut self.scroll_top, row);
+            self.scroll_up(n);
+            self.scroll_top = top;
+            self.move_to(row, 0);
+        }
+    }
+
+    fn insert_chars(&mut self, n: usize) {
+        let (row, col) = (self.cursor.row, self.cursor.col);
+        let n = n.min(self.cols - col);
+        self.grid[row][col..].rotate_right(n);
+        self.blank(row, col..col + n);
+        self.pending_wrap = false;
+    }
+
+    fn delete_chars(&mut self, n: usize) {
+        let (row, col) = (self.cursor.row, self.cursor.col);
+        let n = n.min(self.cols - col);
+        self.grid[row][col..].rotate_left(n);
+        self.blank(row, self.cols - n..self.cols);
+        self.pending_wrap = false;
+    }
+
+    fn erase_display(&mut self, mode: u16) {
+        let (row, col) = (self.cursor.row, self.cursor.col);
+        let rows = match mode {
+            0 => {
+                self.blank(row, col..self.cols);
+                row + 1..self.rows
+            }
+            1 => {
+                self.blank(row, 0..col + 1);
+                0..row
+            }
+            2 | 3 => 0..self.rows,
+            _ => return,
+        };
+        for r in rows {
+            self.blank(r, 0..self.cols);
+        }
+    }
+
+    fn erase_line(&mut self, mode: u16) {
+        let (row, col) = (self.cursor.row, self.cursor.col);
+        match mode {
+            0 => self.blank(row, col..self.cols),
+            1 => self.blank(row, 0..col + 1),
+            2 => self.blank(row, 0..self.cols),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::ansi::Color;
+
+    #[test]
+    fn test_screen() {
+        let mut screen = Screen::new(3, 5);
+
+        println!("        autowrap and scroll:");
+        screen.feed(b"abcdefgh\r\n12345");
+        assert_eq!(screen.text(), "abcde\nfgh\n12345");
+        screen.feed(b"6");
+        assert_eq!(screen.text(), "fgh\n12345\n6");
+
+        println!("        erase and attributes:");
+        screen.feed(b"\x1b[H\x1b[2K\x1b[31mX\x1b[0m\x1b[2;3H\x1b[K");
+        assert_eq!(screen.text(), "X\n12\n6");
+        assert_eq!(screen.cell(0, 0).unwrap().attrs.fg, Color::Indexed(1));
+        assert_eq!(screen.cell(0, 1).unwrap().attrs.fg, Color::Default);
+
+        println!("        scroll region:");
+        screen.feed(b"\x1b[2;3r\x1b[3;1H\nnew\x1b[r");
+        assert_eq!(screen.text(), "X\n6\nnew");
+        screen.feed(b"\x1b[2;1H\x1b[L");
+        assert_eq!(screen.text(), "X\n\n6");
+
+        println!("        utf-8 and insert/delete chars:");
+        screen.feed("\x1b[1;1Hé€".as_bytes());
+        screen.feed(b"\x1b[1;1H\x1b[@\x1b[2;1Hxyz\x1b[2;1H\x1b[P");
+        assert_eq!(screen.text(), " é€\nyz\n6");
+    }
+
+    #[test]
+    fn test_screen_widths() {
+        let mut screen = Screen::new(3, 5);
+
+        println!("        wide characters:");
+        screen.feed("中文字".as_bytes());
+        assert_eq!(screen.text(), "中文\n字\n");
+        assert_eq!(screen.cell(0, 1).unwrap().width, 0);
+        assert_eq!(screen.cell(0, 4).unwrap().ch, ' ');
+        assert_eq!(screen.cursor(), (1, 2));
+
+        println!("        combining marks:");
+        screen.feed("e\u{301}\u{302}\u{303}x".as_bytes());
+        assert_eq!(screen.row_text(1), "字e\u{301}\u{302}x");
+        assert_eq!(screen.cursor(), (1, 4));
+        screen.feed("\x1b[3;1H\u{301}🦀\u{fe0f}".as_bytes());
+        assert_eq!(screen.row_text(2), "🦀\u{fe0f}");
+        assert_eq!(
+            screen.cell(2, 0).unwrap().combining,
+            [Some('\u{fe0f}'), None]
+        );
+
+        println!("        overwriting half of a wide character:");
+        screen.feed(b"\x1b[1;2Hx");
+        assert_eq!(screen.row_text(0), " x文");
+    }
+
+    #[test]
+    fn test_replay() {
+        let mut hb = HistoryBuffer::new(32);
+        hb.add(b"\x1b[2Jone\r\ntwo\r\n");
+        let mark = hb.get_last_index() + 1;
+        hb.add(b"\x1b[2Jthree");
+
+        assert_eq!(Screen::replay(&hb, mark, 3, 8).text(), "one\ntwo\n");
+        assert_eq!(Screen::replay(&hb, 100, 3, 8).text(), "\n\nthree");
+    }
+}
@@ -0,0 +1,187 @@
+//! script(1) typescripts
+//!
+//! Import and export of util-linux `script -T` recordings: a typescript with
+//! the raw output, and a timing file saying how many bytes of it arrived
+//! after how long. Each timing entry maps to one record of a
+//! [`TimedHistory`], so chunk boundaries survive a round trip.
+//!
+//! Two timing formats are understood:
+//!
+//! ```text
+//! 0.250000 6          classic: delay and byte count
+//! O 0.250000 6        advanced: stream, delay and byte count
+//! ```
+//!
+//! Delays are seconds since the previous entry. In the advanced format only
+//! output (`O`) entries read from the typescript; input, header and signal
+//! entries only advance the clock.
+//!
+//! ```rust
+//! use std::time::Duration;
+//! use historybuffer::{ScriptFormat, TimedHistory};
+//!
+//! let mut th = TimedHistory::new(64);
+//! th.add_at(Duration::from_millis(100), b"$ ls\r\n");
+//! th.add_at(Duration::from_millis(350), b"src\r\n");
+//!
+//! let (typescript, timing) = th.to_script(0..100, ScriptFormat::Classic);
+//! assert_eq!(timing, "0.000000 6\n0.250000 5\n");
+//!
+//! let copy = TimedHistory::from_script(&typescript, &timing, 64).unwrap();
+//! assert_eq!(copy.history().get_vec(0, 100), b"$ ls\r\nsrc\r\n");
+//! ```
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use core::ops::Range;
+use core::time::Duration;
+
+use crate::{ParseError, TimedHistory};
+
+// scriptreplay skips the first line of a classic typescript.
+const HEADER_PREFIX: &[u8] = b"Script started";
+
+/// Timing file layout for [`TimedHistory::to_script`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScriptFormat {
+    /// `delay nbytes`, with a header line at the top of the typescript.
+    Classic,
+    /// `O delay nbytes`, as written by `script --logging-format advanced`.
+    Advanced,
+}
+
+impl TimedHistory {
+    /// to_script
+    ///
+    /// Writes the records overlapping range as a typescript and its timing
+    /// file. Delays are measured from the first exported record.
+    pub fn to_script(&self, range: Range<usize>, format: ScriptFormat) -> (Vec<u8>, String) {
+        let mut typescript = Vec::new();
+        let mut timing = String::new();
+        if format == ScriptFormat::Classic {
+            typescript.extend_from_slice(HEADER_PREFIX);
+            typescript.push(b'\n');
+        }
+        let events = self.events(range);
+        let mut previous = events.first().map_or(Duration::ZERO, |&(time, _)| time);
+        for (time, data) in events {
+            let delay = time.saturating_sub(previous).as_secs_f64();
+            previous = previous.max(time);
+            let _ = match format {
+                ScriptFormat::Classic => writeln!(timing, "{delay:.6} {}", data.len()),
+                ScriptFormat::Advanced => writeln!(timing, "O {delay:.6} {}", data.len()),
+            };
+            typescript.extend_from_slice(&data);
+        }
+        (typescript, timing)
+    }
+
+    /// from_script
+    ///
+    /// Replays a typescript and timing file into a new history of at least
+    /// min_buf_size bytes, one record per output entry. A `Script started`
+    /// header line at the top of the typescript is skipped, as is anything
+    /// after the last entry.
+    pub fn from_script(
+        typescript: &[u8],
+        timing: &str,
+        min_buf_size: usize,
+    ) -> Result<TimedHistory, ParseError> {
+        let mut data = typescript;
+        if data.starts_with(HEADER_PREFIX) {
+            let end = data
+                .iter()
+                .position(|&b| b == b'\n')
+                .map_or(data.len(), |i| i + 1);
+            data = &data[end..];
+        }
+
+        let mut th = TimedHistory::new(min_buf_size);
+        let mut clock = Duration::ZERO;
+        for (n, line) in timing.lines().enumerate() {
+            let error = |message| ParseError {
+                line: n + 1,
+                message,
+            };
+            let mut fields = line.split_whitespace();
+            let Some(first) = fields.next() else {
+                continue;
+            };
+            let (output, delay) = match first {
+                "O" | "I" | "S" | "H" => (first == "O", fields.next()),
+                _ => (true, Some(first)),
+            };
+            let delay = delay
+                .and_then(|d| d.parse().ok())
+                .and_then(|d| Duration::try_from_secs_f64(d).ok())
+                .ok_or(error("invalid delay"))?;
+            clock = clock
+                .checked_add(delay)
+                .ok_or(error("total time is too large"))?;
+            if !output {
+                continue;
+            }
+            let len: usize = fields
+                .next()
+                .and_then(|l| l.parse().ok())
+                .ok_or(error("invalid byte count"))?;
+            if len > data.len() {
+                return Err(error("timing runs past the end of the typescript"));
+            }
+            th.add_at(clock, &data[..len]);
+            data = &data[len..];
+        }
+        Ok(th)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_script() {
+        let typescript = b"Script started on 2024-05-01 [COMMAND=\"ls\"]\nabcdefgh\nScript done\n";
+        let timing = "0.5 3\nH 0.000000 TERM xterm\n0.25 2\n1 4\n";
+        let th = TimedHistory::from_script(typescript, timing, 64).unwrap();
+        let ms = Duration::from_millis;
+        assert_eq!(
+            th.events(0..100),
+            vec![
+                (ms(500), b"abc".to_vec()),
+                (ms(750), b"de".to_vec()),
+                (ms(1750), b"fgh\n".to_vec()),
+            ]
+        );
+
+        println!("        advanced export:");
+        let (out, timing) = th.to_script(1..100, ScriptFormat::Advanced);
+        assert_eq!(out, b"bcdefgh\n");
+        assert_eq!(timing, "O 0.000000 2\nO 0.250000 2\nO 1.000000 4\n");
+        let copy = TimedHistory::from_script(&out, &timing, 64).unwrap();
+        assert_eq!(copy.get_time(2), Some(ms(1250)));
+
+        println!("        errors:");
+        let err = TimedHistory::from_script(b"abc", "0.1 2\nI 0.1 5\n0.1 2\n", 64).err();
+        assert_eq!(
+            err,
+            Some(ParseError {
+                line: 3,
+                message: "timing runs past the end of the typescript"
+            })
+        );
+        assert!(TimedHistory::from_script(b"abc", "x 1\n", 64).is_err());
+        let err = TimedHistory::from_script(b"abc", "1e300 1\n", 64).err();
+        assert_eq!(err.map(|e| e.message), Some("invalid delay"));
+        let err = TimedHistory::from_script(b"abc", "1e19 1\n1e19 1\n", 64).err();
+        assert_eq!(
+            err,
+            Some(ParseError {
+                line: 2,
+                message: "total time is too large"
+            })
+        );
+    }
+}
@@ -0,0 +1,311 @@
+//! Selector
+//!
+//! Expands a clicked index into the range a terminal would select: a word
+//! on double click, a line on triple click, or a rectangle of columns for
+//! block selection. Escape sequences are skipped, and columns are counted
+//! with `char_width` on rows soft-wrapped to the given width.
+//!
+//! ```rust
+//! use historybuffer::{HistoryBuffer, Selector};
+//!
+//! let mut hb = HistoryBuffer::new(64);
+//! hb.add(b"cargo build --release   \nerror: oops\n");
+//!
+//! let sel = Selector::new();
+//! let word = sel.select_word(&hb, 15);
+//! assert_eq!(word, 12..21);
+//! assert_eq!(sel.selected_text(&hb, word), "--release");
+//! assert_eq!(sel.selected_text(&hb, sel.select_line(&hb, 3)), "cargo build --release");
+//! ```
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::wrap::{TAB, TAB_WIDTH, line_chars, wrap_breaks};
+use crate::{HistoryBuffer, char_width};
+
+const DEFAULT_WORD_CHARS: &str = "_-./~";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Class {
+    Word,
+    Space,
+    Other(char),
+}
+
+// A character laid out on a display row.
+#[derive(Debug, Clone, Copy)]
+struct Placed {
+    index: usize,
+    end: usize,
+    col: usize,
+    width: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selector {
+    word_chars: String,
+}
+
+impl Default for Selector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Selector {
+    pub fn new() -> Self {
+        Self {
+            word_chars: DEFAULT_WORD_CHARS.to_string(),
+        }
+    }
+
+    /// word_chars
+    ///
+    /// Sets the characters, besides letters and digits, that are part of a word.
+    pub fn word_chars(mut self, chars: &str) -> Self {
+        self.word_chars = chars.to_string();
+        self
+    }
+
+    /// is_word_char
+    pub fn is_word_char(&self, c: char) -> bool {
+        c.is_alphanumeric() || self.word_chars.contains(c)
+    }
+
+    /// select_word
+    ///
+    /// Expands index to the word around it. A run of blanks is selected as a
+    /// whole; any other character is selected on its own. Returns an empty
+    /// range if index is not on a character.
+    pub fn select_word(&self, hb: &HistoryBuffer, index: usize) -> Range<usize> {
+        let line = line_range(hb, index);
+        let chars = char_spans(hb, &line);
+        let Some(pos) = chars.iter().position(|(span, _)| span.contains(&index)) else {
+            return index..index;
+        };
+        let class = self.class(chars[pos].1);
+        if let Class::Other(_) = class {
+            return chars[pos].0.clone();
+        }
+        let mut first = pos;
+        while first > 0 && self.class(chars[first - 1].1) == class {
+            first -= 1;
+        }
+        let mut last = pos;
+        while last + 1 < chars.len() && self.class(chars[last + 1].1) == class {
+            last += 1;
+        }
+        chars[first].0.start..chars[last].0.end
+    }
+
+    /// select_line
+    ///
+    /// Expands index to the logical line around it, without the newline.
+    /// Soft-wrapped rows of the same line are selected together.
+    pub fn select_line(&self, hb: &HistoryBuffer, index: usize) -> Range<usize> {
+        line_range(hb, index)
+    }
+
+    /// select_block
+    ///
+    /// Returns one range per display row for a rectangular selection with
+    /// corners at the from and to indices, on rows wrapped to width. Rows
+    /// with nothing in the selected columns get an empty range.
+    pub fn select_block(
+        &self,
+        hb: &HistoryBuffer,
+        from: usize,
+        to: usize,
+        width: usize,
+    ) -> Vec<Range<usize>> {
+        let (top, bottom) = (from.min(to), from.max(to));
+        let mut rows = Vec::new();
+        let mut start = line_range(hb, top).start;
+        let last = line_range(hb, bottom).end;
+        loop {
+            let line = line_range(hb, start);
+            rows.extend(layout(hb, &line, width.max(1)));
+            if line.end >= last {
+                break;
+            }
+            start = line.end + 1;
+        }
+
+        let (r1, c1) = locate(&rows, from);
+        let (r2, c2) = locate(&rows, to);
+        let (lo, hi) = (c1.min(c2), c1.max(c2));
+        rows[r1.min(r2)..=r1.max(r2)]
+            .iter()
+            .map(|(row_start, cells)| {
+                let mut hits = cells.iter().filter(|p| p.col <= hi && p.col + p.width > lo);
+                match hits.next() {
+                    Some(first) => first.index..hits.next_back().unwrap_or(first).end,
+                    None => *row_start..*row_start,
+                }
+            })
+            .collect()
+    }
+
+    /// selected_text
+    ///
+    /// Gets the text in range with escape sequences removed and trailing
+    /// whitespace trimmed from every line.
+    pub fn selected_text(&self, hb: &HistoryBuffer, range: Range<usize>) -> String {
+        let range = hb.align_utf8(range);
+        let plain = hb.get_plain_vec(range.start, range.len());
+        let text = String::from_utf8_lossy(&plain);
+        text.split('\n')
+            .map(str::trim_end)
+            .collect::<Vec<&str>>()
+            .join("\n")
+    }
+
+    /// block_text
+    ///
+    /// Joins the text of the ranges from `select_block` into one line each.
+    pub fn block_text(&self, hb: &HistoryBuffer, ranges: &[Range<usize>]) -> String {
+        ranges
+            .iter()
+            .map(|range| self.selected_text(hb, range.clone()))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    fn class(&self, c: char) -> Class {
+        if c.is_whitespace() {
+            Class::Space
+        } else if self.is_word_char(c) {
+            Class::Word
+        } else {
+            Class::Other(c)
+        }
+    }
+}
+
+// Line containing index, without its newline.
+fn line_range(hb: &HistoryBuffer, index: usize) -> Range<usize> {
+    let oldest = hb.get_index();
+    let index = index.clamp(oldest, hb.next_running);
+    let mut start = index;
+    while start > oldest && hb.get(start - 1) != Some(b'\n') {
+        start -= 1;
+    }
+    let mut end = index;
+    while end < hb.next_running && hb.get(end) != Some(b'\n') {
+        end += 1;
+    }
+    start..end
+}
+
+// Characters of a line with the raw bytes each one was drawn from.
+fn char_spans(hb: &HistoryBuffer, line: &Range<usize>) -> Vec<(Range<usize>, char)> {
+    let chars = line_chars(hb, line.start, line.end);
+    chars
+        .iter()
+        .enumerate()
+        .map(|(i, &(index, c))| {
+            let next = chars.get(i + 1).map_or(line.end, |&(next, _)| next);
+            (index..(index + c.len_utf8()).min(next), c)
+        })
+        .collect()
+}
+
+// Display rows of a line as (row start, characters).
+fn layout(hb: &HistoryBuffer, line: &Range<usize>, width: usize) -> Vec<(usize, Vec<Placed>)> {
+    let chars = char_spans(hb, line);
+    let widths: Vec<u8> = chars
+        .iter()
+        .map(|&(_, c)| if c == '\t' { TAB } else { char_width(c) as u8 })
+        .collect();
+    let breaks = wrap_breaks(&widths, width);
+    let mut rows = Vec::with_capacity(breaks.len());
+    for (n, &first) in breaks.iter().enumerate() {
+        let last = breaks.get(n + 1).copied().unwrap_or(chars.len());
+        let row_start = if n == 0 {
+            line.start
+        } else {
+            chars[first].0.start
+        };
+        let mut col = 0;
+        let mut cells = Vec::with_capacity(last - first);
+        for (span, &w) in chars[first..last]
+            .iter()
+            .map(|(span, _)| span)
+            .zip(&widths[first..last])
+        {
+            let w = if w == TAB {
+                TAB_WIDTH - col % TAB_WIDTH
+            } else {
+                w as usize
+            };
+            cells.push(Placed {
+                index: span.start,
+                end: span.end,
+                col,
+                width: w,
+            });
+            col += w;
+        }
+        rows.push((row_start, cells));
+    }
+    rows
+}
+
+// Row and column of the character at index.
+fn locate(rows: &[(usize, Vec<Placed>)], index: usize) -> (usize, usize) {
+    let row = rows
+        .iter()
+        .rposition(|&(start, _)| start <= index)
+        .unwrap_or(0);
+    let col = rows[row]
+        .1
+        .iter()
+        .rev()
+        .find(|p| p.index <= index)
+        .map_or(0, |p| {
+            if index < p.end {
+                p.col
+            } else {
+                p.col + p.width
+            }
+        });
+    (row, col)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_selection() {
+        let mut hb = HistoryBuffer::new(128);
+        hb.add(b"ls \x1b[1m/usr/bin\x1b[0m  foo=bar\n");
+        hb.add("日本 text\n".as_bytes());
+        hb.add(b"abcdefgh\n");
+        let sel = Selector::new();
+
+        println!("        words:");
+        assert_eq!(sel.selected_text(&hb, sel.select_word(&hb, 10)), "/usr/bin");
+        assert_eq!(sel.select_word(&hb, 21), 21..24);
+        assert_eq!(sel.select_word(&hb, 24), 24..25);
+        assert_eq!(sel.select_word(&hb, 20), 19..21);
+        assert_eq!(sel.select_word(&hb, 28), 28..28);
+        let eq = Selector::new().word_chars("=");
+        assert_eq!(sel.selected_text(&hb, eq.select_word(&hb, 24)), "foo=bar");
+        assert_eq!(sel.selected_text(&hb, sel.select_word(&hb, 30)), "日本");
+
+        println!("        lines:");
+        assert_eq!(sel.select_line(&hb, 36), 29..40);
+        assert_eq!(sel.selected_text(&hb, 21..43), "foo=bar\n日本 text\nab");
+
+        println!("        block:");
+        let block = sel.select_block(&hb, 47, 29, 5);
+        assert_eq!(block.len(), 4);
+        assert_eq!(sel.block_text(&hb, &block), "日\nte\nab\nfg");
+        let block = sel.select_block(&hb, 32, 44, 80);
+        assert_eq!(sel.block_text(&hb, &block), "本\ncd");
+    }
+}
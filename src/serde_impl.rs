@@ -0,0 +1,253 @@
+//! serde support
+//!
+//! With the `serde` feature, a [`HistoryBuffer`] serializes as its capacity,
+//! the index of its oldest byte and its live bytes:
+//!
+//! ```text
+//! {"capacity": 8, "start_index": 13, "data": [72, 105, ...]}
+//! ```
+//!
+//! Overwritten and cleared bytes are not written. Deserializing checks that
+//! the capacity is one `new` could have made and that the data fits in it.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+use crate::HistoryBuffer;
+
+const FIELDS: &[&str] = &["capacity", "start_index", "data"];
+const MAX_CAPACITY: usize = 1 << 23;
+
+// Live bytes, serialized with serialize_bytes.
+struct Bytes<'a>(&'a [u8]);
+
+impl Serialize for Bytes<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+impl Serialize for HistoryBuffer {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let data = self.get_recent(self.len);
+        let mut s = serializer.serialize_struct("HistoryBuffer", FIELDS.len())?;
+        s.serialize_field("capacity", &self.buf.len())?;
+        s.serialize_field("start_index", &self.get_index())?;
+        s.serialize_field("data", &Bytes(&data))?;
+        s.end()
+    }
+}
+
+// Accepts bytes in either the bytes or the sequence form.
+struct ByteBuf(Vec<u8>);
+
+impl<'de> Deserialize<'de> for ByteBuf {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ByteBufVisitor;
+
+        impl<'de> Visitor<'de> for ByteBufVisitor {
+            type Value = ByteBuf;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("bytes")
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<ByteBuf, E> {
+                Ok(ByteBuf(v.to_vec()))
+            }
+
+            fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<ByteBuf, E> {
+                Ok(ByteBuf(v))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<ByteBuf, A::Error> {
+                let mut v = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(MAX_CAPACITY));
+                while let Some(byte) = seq.next_element()? {
+                    v.push(byte);
+                }
+                Ok(ByteBuf(v))
+            }
+        }
+
+        deserializer.deserialize_bytes(ByteBufVisitor)
+    }
+}
+
+fn restore<E: de::Error>(
+    capacity: usize,
+    start_index: usize,
+    data: Vec<u8>,
+) -> Result<HistoryBuffer, E> {
+    if capacity != 0 && (!capacity.is_power_of_two() || !(2..=MAX_CAPACITY).contains(&capacity)) {
+        return Err(E::invalid_value(
+            de::Unexpected::Unsigned(capacity as u64),
+            &"0 or a power of two from 2 to 2^23",
+        ));
+    }
+    if data.len() > capacity {
+        return Err(E::invalid_length(
+            data.len(),
+            &"no more bytes than the capacity",
+        ));
+    }
+    if start_index.checked_add(data.len()).is_none() {
+        return Err(E::custom("start_index + data length overflows"));
+    }
+    let mut hb = if capacity == 0 {
+        HistoryBuffer::default()
+    } else {
+        HistoryBuffer::new(capacity)
+    };
+    hb.next_running = start_index;
+    hb.add(&data);
+    Ok(hb)
+}
+
+impl<'de> Deserialize<'de> for HistoryBuffer {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        enum Field {
+            Capacity,
+            StartIndex,
+            Data,
+        }
+
+        impl<'de> Deserialize<'de> for Field {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                struct FieldVisitor;
+
+                impl Visitor<'_> for FieldVisitor {
+                    type Value = Field;
+
+                    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                        f.write_str("`capacity`, `start_index` or `data`")
+                    }
+
+                    fn visit_str<E: de::Error>(self, v: &str) -> Result<Field, E> {
+                        match v {
+                            "capacity" => Ok(Field::Capacity),
+                            "start_index" => Ok(Field::StartIndex),
+                            "data" => Ok(Field::Data),
+                            _ => Err(E::unknown_field(v, FIELDS)),
+                        }
+                    }
+                }
+
+                deserializer.deserialize_identifier(FieldVisitor)
+            }
+        }
+
+        struct HistoryBufferVisitor;
+
+        impl<'de> Visitor<'de> for HistoryBufferVisitor {
+            type Value = HistoryBuffer;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("struct HistoryBuffer")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<HistoryBuffer, A::Error> {
+                let capacity = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+                let start_index = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+                let ByteBuf(data) = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(2, &self))?;
+                restore(capacity, start_index, data)
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<HistoryBuffer, A::Error> {
+                let (mut capacity, mut start_index, mut data) = (None, None, None);
+                while let Some(key) = map.next_key()? {
+                    match key {
+                        Field::Capacity if capacity.is_none() => capacity = Some(map.next_value()?),
+                        Field::StartIndex if start_index.is_none() => {
+                            start_index = Some(map.next_value()?)
+                        }
+                        Field::Data if data.is_none() => {
+                            data = Some(map.next_value::<ByteBuf>()?.0)
+                        }
+                        Field::Capacity => return Err(de::Error::duplicate_field("capacity")),
+                        Field::StartIndex => return Err(de::Error::duplicate_field("start_index")),
+                        Field::Data => return Err(de::Error::duplicate_field("data")),
+                    }
+                }
+                restore(
+                    capacity.ok_or_else(|| de::Error::missing_field("capacity"))?,
+                    start_index.ok_or_else(|| de::Error::missing_field("start_index"))?,
+                    data.ok_or_else(|| de::Error::missing_field("data"))?,
+                )
+            }
+        }
+
+        deserializer.deserialize_struct("HistoryBuffer", FIELDS, HistoryBufferVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use serde_test::{Token, assert_de_tokens_error, assert_tokens};
+
+    #[test]
+    fn test_serde() {
+        let mut hb = HistoryBuffer::new(8);
+        hb.add(b"The Terminal History.");
+        assert_tokens(
+            &hb,
+            &[
+                Token::Struct {
+                    name: "HistoryBuffer",
+                    len: 3,
+                },
+                Token::Str("capacity"),
+                Token::U64(8),
+                Token::Str("start_index"),
+                Token::U64(13),
+                Token::Str("data"),
+                Token::Bytes(b"History."),
+                Token::StructEnd,
+            ],
+        );
+
+        println!("        validation:");
+        assert_de_tokens_error::<HistoryBuffer>(
+            &[
+                Token::Struct {
+                    name: "HistoryBuffer",
+                    len: 3,
+                },
+                Token::Str("capacity"),
+                Token::U64(2),
+                Token::Str("start_index"),
+                Token::U64(0),
+                Token::Str("data"),
+                Token::Bytes(b"abc"),
+                Token::StructEnd,
+            ],
+            "invalid length 3, expected no more bytes than the capacity",
+        );
+        assert_de_tokens_error::<HistoryBuffer>(
+            &[
+                Token::Struct {
+                    name: "HistoryBuffer",
+                    len: 3,
+                },
+                Token::Str("capacity"),
+                Token::U64(6),
+                Token::Str("start_index"),
+                Token::U64(0),
+                Token::Str("data"),
+                Token::Bytes(b""),
+                Token::StructEnd,
+            ],
+            "invalid value: integer `6`, expected 0 or a power of two from 2 to 2^23",
+        );
+    }
+}
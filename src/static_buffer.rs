@@ -0,0 +1,102 @@
+//! StaticHistoryBuffer
+//!
+//! A history buffer whose storage is an inline `[u8; N]`, for targets
+//! without a heap. `N` must be a power of two of at least 2, which is
+//! checked when the program is compiled, and `new` is a `const fn`, so the
+//! buffer can be placed in a `static`.
+//!
+//! It has the same methods and indices as
+//! [`HistoryBuffer`](crate::HistoryBuffer). Without the `alloc` feature,
+//! `get_slice_and_index` and `get_recent_slice` read into a slice the caller
+//! provides.
+//!
+//! ```rust
+//! use historybuffer::StaticHistoryBuffer;
+//!
+//! static EMPTY: StaticHistoryBuffer<1024> = StaticHistoryBuffer::new();
+//! assert_eq!(EMPTY.get_len(), 0);
+//!
+//! let mut hb = StaticHistoryBuffer::<8>::new();
+//! hb.add(b"The Terminal History.");
+//!
+//! let mut out = [0; 6];
+//! assert_eq!(hb.get_slice_and_index(15, &mut out), (&b"story."[..], 15));
+//! assert_eq!(hb.get_recent_slice(&mut out[..4]), b"ory.");
+//! assert_eq!(hb.get_index(), 13);
+//! ```
+//!
+//! Other sizes are rejected:
+//!
+//! ```compile_fail
+//! let hb = historybuffer::StaticHistoryBuffer::<6>::new();
+//! ```
+
+use crate::HistoryRing;
+
+/// A [`HistoryRing`] stored in an inline array of N bytes.
+pub type StaticHistoryBuffer<const N: usize> = HistoryRing<[u8; N]>;
+
+impl<const N: usize> HistoryRing<[u8; N]> {
+    const POWER_OF_TWO: () = assert!(
+        N >= 2 && N.is_power_of_two(),
+        "StaticHistoryBuffer size must be a power of two of at least 2"
+    );
+
+    pub const fn new() -> Self {
+        let () = Self::POWER_OF_TWO;
+        Self {
+            buf: [0; N],
+            len: 0,
+            next_running: 0,
+        }
+    }
+}
+
+impl<const N: usize> Default for HistoryRing<[u8; N]> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_static_history_buffer() {
+        let mut hb = StaticHistoryBuffer::<8>::new();
+        let mut out = [0; 16];
+        assert_eq!(hb.get_slice_and_index(0, &mut out), (&[][..], 0));
+        assert_eq!(hb.last_byte(), None);
+
+        hb.add(b"The Terminal History.");
+        assert_eq!(hb.get_slice_and_index(13, &mut out), (&b"History."[..], 13));
+        assert_eq!(hb.get_slice_and_index(0, &mut out), (&b"His"[..], 13));
+        assert_eq!(hb.get_slice_and_index(15, &mut out[..2]), (&b"st"[..], 15));
+        assert_eq!(hb.get(13), Some(b'H'));
+        assert_eq!(hb.get(12), None);
+        assert_eq!(hb.get_last_index(), 20);
+        assert_eq!(hb.last_byte(), Some(b'.'));
+
+        println!("        wrapped:");
+        hb.add(b" and");
+        assert_eq!(hb.as_slices(), (&b"ory. an"[..], &b"d"[..]));
+        assert_eq!(hb.get_recent_slice(&mut out), b"ory. and");
+        assert_eq!(hb.get_recent_slice(&mut out[..3]), b"and");
+
+        println!("        cleared:");
+        hb.clear_at(19);
+        assert_eq!(hb.get_index(), 19);
+        assert_eq!(hb.get_recent_slice(&mut out), b"y. and");
+        #[cfg(feature = "alloc")]
+        assert_eq!(hb.get_recent(4), b" and");
+        let mut other = StaticHistoryBuffer::<8>::new();
+        other.add(b"-------------------y. and");
+        other.clear_at(19);
+        assert_eq!(hb, other);
+        hb.clear();
+        assert_eq!(hb.get_len(), 0);
+        assert_eq!(hb.get_last_index(), 25);
+    }
+}
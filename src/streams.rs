@@ -0,0 +1,198 @@
+//! MultiStreamHistory
+//!
+//! Several streams (stdout, stderr, stdin, ...) interleaved into one
+//! [`HistoryBuffer`] so they share a single index space and keep their exact
+//! ordering, while remembering which stream each byte came from.
+//!
+//! Attribution is stored as runs: consecutive bytes from the same stream
+//! cost one entry no matter how many `add_to` calls produced them.
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::HistoryBuffer;
+
+/// Identifies the stream a byte came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StreamId(pub u8);
+
+impl StreamId {
+    pub const STDIN: StreamId = StreamId(0);
+    pub const STDOUT: StreamId = StreamId(1);
+    pub const STDERR: StreamId = StreamId(2);
+}
+
+/// Consecutive bytes from one stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamRun {
+    pub stream: StreamId,
+    pub start_index: usize,
+    pub data: Vec<u8>,
+}
+
+impl StreamRun {
+    /// Absolute index range of the run's data.
+    pub fn range(&self) -> Range<usize> {
+        self.start_index..self.start_index + self.data.len()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MultiStreamHistory {
+    hb: HistoryBuffer,
+    runs: VecDeque<(usize, StreamId)>, // (start index, stream)
+}
+
+impl MultiStreamHistory {
+    pub fn new(min_buf_size: usize) -> Self {
+        Self {
+            hb: HistoryBuffer::new(min_buf_size),
+            ..Default::default()
+        }
+    }
+
+    /// add_to
+    ///
+    /// Appends data from stream to the shared history.
+    pub fn add_to(&mut self, stream: StreamId, data: &[u8]) {
+        if data.is_empty() {
+            return;
+        }
+        if self.runs.back().map(|run| run.1) != Some(stream) {
+            self.runs.push_back((self.hb.next_running, stream));
+        }
+        self.hb.add(data);
+        self.evict();
+    }
+
+    /// clear the buffer and its stream attribution
+    pub fn clear(&mut self) {
+        self.hb.clear();
+        self.evict();
+    }
+
+    /// clear_at the buffer at a specific point
+    pub fn clear_at(&mut self, new_start_index: usize) {
+        self.hb.clear_at(new_start_index);
+        self.evict();
+    }
+
+    /// history
+    ///
+    /// Gives read access to the underlying interleaved bytes.
+    pub fn history(&self) -> &HistoryBuffer {
+        &self.hb
+    }
+
+    /// stream_at
+    ///
+    /// Gets the stream the byte at index came from.
+    pub fn stream_at(&self, index: usize) -> Option<StreamId> {
+        self.hb.get(index)?;
+        let pos = self.runs.partition_point(|run| run.0 <= index);
+        Some(self.runs[pos.checked_sub(1)?].1)
+    }
+
+    /// get_runs
+    ///
+    /// Returns the history in a range split into per-stream runs, in the
+    /// order the bytes were added. The first run starts at the same index
+    /// `get_vec_and_index` would return.
+    pub fn get_runs(&self, start_index: usize, max_len: usize) -> Vec<StreamRun> {
+        let (data, inn) = self.hb.get_vec_and_index(start_index, max_len);
+        let mut out = Vec::new();
+        if data.is_empty() {
+            return out;
+        }
+        let end = inn + data.len();
+        let first = self
+            .runs
+            .partition_point(|run| run.0 <= inn)
+            .saturating_sub(1);
+        for (i, &(run_start, stream)) in self.runs.iter().enumerate().skip(first) {
+            if run_start >= end {
+                break;
+            }
+            let run_end = self.runs.get(i + 1).map_or(end, |next| next.0.min(end));
+            let run_start = run_start.max(inn);
+            out.push(StreamRun {
+                stream,
+                start_index: run_start,
+                data: data[run_start - inn..run_end - inn].to_vec(),
+            });
+        }
+        out
+    }
+
+    /// get_stream_vec
+    ///
+    /// Returns only the bytes from stream within a range of the shared history.
+    pub fn get_stream_vec(&self, stream: StreamId, start_index: usize, max_len: usize) -> Vec<u8> {
+        self.get_runs(start_index, max_len)
+            .into_iter()
+            .filter(|run| run.stream == stream)
+            .flat_map(|run| run.data)
+            .collect()
+    }
+
+    // Drop runs whose bytes are no longer in memory.
+    fn evict(&mut self) {
+        let oldest = self.hb.get_index();
+        while !self.runs.is_empty() {
+            let end = self.runs.get(1).map_or(self.hb.next_running, |next| next.0);
+            if end > oldest {
+                break;
+            }
+            self.runs.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_streams() {
+        let mut ms = MultiStreamHistory::new(16);
+        ms.add_to(StreamId::STDOUT, b"ok ");
+        ms.add_to(StreamId::STDOUT, b"1\n");
+        ms.add_to(StreamId::STDERR, b"bad\n");
+        ms.add_to(StreamId::STDOUT, b"ok 2\n");
+
+        assert_eq!(ms.stream_at(0), Some(StreamId::STDOUT));
+        assert_eq!(ms.stream_at(5), Some(StreamId::STDERR));
+        assert_eq!(ms.stream_at(14), None);
+        assert_eq!(
+            ms.get_runs(4, 6),
+            vec![
+                StreamRun {
+                    stream: StreamId::STDOUT,
+                    start_index: 4,
+                    data: b"\n".to_vec(),
+                },
+                StreamRun {
+                    stream: StreamId::STDERR,
+                    start_index: 5,
+                    data: b"bad\n".to_vec(),
+                },
+                StreamRun {
+                    stream: StreamId::STDOUT,
+                    start_index: 9,
+                    data: b"o".to_vec(),
+                },
+            ]
+        );
+        assert_eq!(ms.get_stream_vec(StreamId::STDOUT, 0, 100), b"ok 1\nok 2\n");
+
+        println!("        evicted:");
+        ms.add_to(StreamId::STDIN, b"quit\n");
+        assert_eq!(ms.history().get_index(), 3);
+        assert_eq!(ms.get_stream_vec(StreamId::STDOUT, 0, 100), b"1\nok 2\n");
+        assert_eq!(ms.get_runs(0, 100).len(), 4);
+        ms.clear_at(9);
+        assert_eq!(ms.get_runs(0, 100).len(), 2);
+    }
+}
@@ -0,0 +1,168 @@
+//! TaggedHistory
+//!
+//! A [`HistoryBuffer`] with metadata spans. Any value can be attached to a
+//! range of absolute indices and queried back by index or by predicate.
+//! Spans are trimmed as their bytes are overwritten by `add` or hidden by
+//! `clear_at`, and dropped once nothing of them is left.
+
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::HistoryBuffer;
+
+/// A tag attached to a range of absolute indices.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span<T> {
+    pub range: Range<usize>,
+    pub tag: T,
+}
+
+#[derive(Debug)]
+pub struct TaggedHistory<T> {
+    hb: HistoryBuffer,
+    spans: Vec<Span<T>>, // Sorted by range.start
+}
+
+impl<T> TaggedHistory<T> {
+    pub fn new(min_buf_size: usize) -> Self {
+        Self {
+            hb: HistoryBuffer::new(min_buf_size),
+            spans: Vec::new(),
+        }
+    }
+
+    /// add
+    ///
+    /// Adds data to the history, trimming spans whose bytes were overwritten.
+    pub fn add(&mut self, data: &[u8]) {
+        self.hb.add(data);
+        self.evict();
+    }
+
+    /// add_tagged
+    ///
+    /// Adds data and tags the bytes it occupies.
+    pub fn add_tagged(&mut self, data: &[u8], tag: T) {
+        let start = self.hb.next_running;
+        self.add(data);
+        self.tag_range(start..self.hb.next_running, tag);
+    }
+
+    /// clear the buffer and drop every span
+    pub fn clear(&mut self) {
+        self.hb.clear();
+        self.evict();
+    }
+
+    /// clear_at the buffer at a specific point, trimming spans before it
+    pub fn clear_at(&mut self, new_start_index: usize) {
+        self.hb.clear_at(new_start_index);
+        self.evict();
+    }
+
+    /// history
+    ///
+    /// Gives read access to the underlying byte history.
+    pub fn history(&self) -> &HistoryBuffer {
+        &self.hb
+    }
+
+    /// tag_range
+    ///
+    /// Attaches tag to range. The part of the range that has already been
+    /// overwritten is ignored; the range may extend past the newest byte.
+    pub fn tag_range(&mut self, range: Range<usize>, tag: T) {
+        let range = range.start.max(self.hb.get_index())..range.end;
+        if range.is_empty() {
+            return;
+        }
+        let pos = self
+            .spans
+            .partition_point(|span| span.range.start <= range.start);
+        self.spans.insert(pos, Span { range, tag });
+    }
+
+    /// tags_at
+    ///
+    /// Iterates the tags covering the byte at index, oldest span first.
+    pub fn tags_at(&self, index: usize) -> impl Iterator<Item = &T> + '_ {
+        let end = self.spans.partition_point(|span| span.range.start <= index);
+        self.spans[..end]
+            .iter()
+            .filter(move |span| span.range.end > index)
+            .map(|span| &span.tag)
+    }
+
+    /// ranges_with
+    ///
+    /// Iterates the ranges whose tag matches predicate, ordered by start index.
+    pub fn ranges_with<'a, P>(&'a self, predicate: P) -> impl Iterator<Item = Range<usize>> + 'a
+    where
+        P: Fn(&T) -> bool + 'a,
+    {
+        self.spans
+            .iter()
+            .filter(move |span| predicate(&span.tag))
+            .map(|span| span.range.clone())
+    }
+
+    /// spans
+    ///
+    /// Iterates every live span, ordered by start index.
+    pub fn spans(&self) -> impl Iterator<Item = &Span<T>> + '_ {
+        self.spans.iter()
+    }
+
+    // Trim spans to the live history and drop the ones that are gone.
+    fn evict(&mut self) {
+        let oldest = self.hb.get_index();
+        self.spans.retain_mut(|span| {
+            span.range.start = span.range.start.max(oldest);
+            !span.range.is_empty()
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Stream {
+        Stdout,
+        Stderr,
+    }
+
+    #[test]
+    fn test_tags() {
+        let mut th = TaggedHistory::new(16);
+        th.add_tagged(b"building\n", Stream::Stdout);
+        th.add_tagged(b"error!\n", Stream::Stderr);
+        th.tag_range(9..14, Stream::Stdout);
+
+        assert_eq!(th.tags_at(3).collect::<Vec<_>>(), vec![&Stream::Stdout]);
+        assert_eq!(
+            th.tags_at(10).collect::<Vec<_>>(),
+            vec![&Stream::Stderr, &Stream::Stdout]
+        );
+        assert_eq!(th.tags_at(16).count(), 0);
+        assert_eq!(
+            th.ranges_with(|s| *s == Stream::Stderr).collect::<Vec<_>>(),
+            vec![9..16]
+        );
+
+        println!("        trimmed by add:");
+        th.add(b"done\n");
+        assert_eq!(
+            th.ranges_with(|s| *s == Stream::Stdout).collect::<Vec<_>>(),
+            vec![5..9, 9..14]
+        );
+
+        println!("        dropped by clear_at:");
+        th.clear_at(14);
+        assert_eq!(th.spans().count(), 1);
+        th.clear();
+        assert_eq!(th.spans().count(), 0);
+    }
+}
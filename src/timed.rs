@@ -0,0 +1,170 @@
+//! TimedHistory
+//!
+//! A [`RecordBuffer`] that also remembers when each `add` happened, as a
+//! [`Duration`] since the recording started. This is what recording formats
+//! such as asciicast need to replay output at its original pace.
+//!
+//! `add` stamps data with the time elapsed since `new`; `add_at` takes the
+//! time from the caller, for example when importing a recording. Without the
+//! `std` feature there is no clock, so only `add_at` is available.
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+use crate::{HistoryBuffer, RecordBuffer};
+
+/// An import failed: what was wrong, and on which line (starting at 1).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: &'static str,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl core::error::Error for ParseError {}
+
+#[derive(Debug)]
+pub struct TimedHistory {
+    rb: RecordBuffer,
+    times: VecDeque<Duration>,
+    #[cfg(feature = "std")]
+    started: Instant,
+}
+
+impl TimedHistory {
+    pub fn new(min_buf_size: usize) -> Self {
+        Self {
+            rb: RecordBuffer::new(min_buf_size),
+            times: VecDeque::new(),
+            #[cfg(feature = "std")]
+            started: Instant::now(),
+        }
+    }
+
+    /// add
+    ///
+    /// Adds data stamped with the time since the history was created, and
+    /// returns its sequence number.
+    #[cfg(feature = "std")]
+    pub fn add(&mut self, data: &[u8]) -> Option<usize> {
+        self.add_at(self.started.elapsed(), data)
+    }
+
+    /// add_at
+    ///
+    /// Adds data stamped with a given time since the recording started.
+    pub fn add_at(&mut self, time: Duration, data: &[u8]) -> Option<usize> {
+        let seq = self.rb.add(data)?;
+        self.times.push_back(time);
+        self.evict();
+        Some(seq)
+    }
+
+    /// clear the buffer and drop every record
+    pub fn clear(&mut self) {
+        self.rb.clear();
+        self.evict();
+    }
+
+    /// clear_at the buffer at a specific point
+    pub fn clear_at(&mut self, new_start_index: usize) {
+        self.rb.clear_at(new_start_index);
+        self.evict();
+    }
+
+    /// history
+    ///
+    /// Gives read access to the underlying byte history.
+    pub fn history(&self) -> &HistoryBuffer {
+        self.rb.history()
+    }
+
+    /// records
+    ///
+    /// Gives read access to the records, one per `add`.
+    pub fn records(&self) -> &RecordBuffer {
+        &self.rb
+    }
+
+    /// get_time
+    ///
+    /// Gets the time a record was added.
+    pub fn get_time(&self, seq: usize) -> Option<Duration> {
+        let pos = seq.checked_sub(self.rb.get_first_seq())?;
+        self.times.get(pos).copied()
+    }
+
+    /// events
+    ///
+    /// Returns the bytes of each record overlapping range, clipped to it and
+    /// to what is still in memory, with the time the record was added.
+    pub fn events(&self, range: Range<usize>) -> Vec<(Duration, Vec<u8>)> {
+        let hb = self.rb.history();
+        let start = range.start.max(hb.get_index());
+        let end = range.end.min(hb.next_running);
+        let mut events = Vec::new();
+        for (seq, &time) in (self.rb.get_first_seq()..).zip(&self.times) {
+            let Some(record) = self.rb.get_record_range(seq) else {
+                break;
+            };
+            let (a, b) = (record.start.max(start), record.end.min(end));
+            if a < b {
+                events.push((time, hb.get_vec(a, b - a)));
+            }
+        }
+        events
+    }
+
+    // Keep one time per live record.
+    fn evict(&mut self) {
+        let live = self.rb.get_next_seq() - self.rb.get_first_seq();
+        while self.times.len() > live {
+            self.times.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_timed_history() {
+        let mut th = TimedHistory::new(8);
+        let ms = Duration::from_millis;
+        th.add_at(ms(0), b"ab");
+        th.add_at(ms(10), b"cdefg");
+        th.add_at(ms(25), b"hij");
+
+        assert_eq!(th.records().get_first_seq(), 1);
+        assert_eq!(th.get_time(0), None);
+        assert_eq!(th.get_time(2), Some(ms(25)));
+        assert_eq!(
+            th.events(0..100),
+            vec![(ms(10), b"cdefg".to_vec()), (ms(25), b"hij".to_vec())]
+        );
+        assert_eq!(
+            th.events(5..8),
+            vec![(ms(10), b"fg".to_vec()), (ms(25), b"h".to_vec())]
+        );
+
+        th.clear();
+        assert_eq!(th.events(0..100), vec![]);
+        #[cfg(feature = "std")]
+        {
+            assert_eq!(th.add(b"x"), Some(3));
+            assert!(th.get_time(3).is_some());
+        }
+    }
+}
@@ -0,0 +1,421 @@
+//! UTF-8 helpers
+//!
+//! An incremental decoder for byte streams that may contain invalid or
+//! truncated sequences. Invalid input is replaced with U+FFFD, one
+//! replacement per maximal invalid subsequence, matching `String::from_utf8_lossy`.
+//!
+//! `char_width` gives the number of terminal cells a character occupies.
+//!
+//! `HistoryBuffer::get_str_lossy` and `get_utf8` read text without splitting
+//! a character, even when eviction or a range boundary lands inside one:
+//!
+//! ```rust
+//! use historybuffer::HistoryBuffer;
+//!
+//! let mut hb = HistoryBuffer::new(8);
+//! hb.add("añb€c".as_bytes()); // 8 bytes: a ñ(2) b €(3) c
+//!
+//! let (text, range) = hb.get_str_lossy(2..7);
+//! assert_eq!(text, "b€");
+//! assert_eq!(range, 3..7);
+//! assert_eq!(hb.chars_rev().collect::<String>(), "c€bña");
+//! ```
+
+use alloc::borrow::Cow;
+use alloc::string::String;
+use core::char::REPLACEMENT_CHARACTER;
+use core::ops::Range;
+
+use crate::{HistoryBuffer, ring};
+
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Utf8Decoder {
+    cp: u32,
+    need: u8,
+    lower: u8, // Allowed range of the next continuation byte
+    upper: u8,
+}
+
+impl Utf8Decoder {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds one byte, calling out with each decoded character.
+    pub(crate) fn feed(&mut self, byte: u8, mut out: impl FnMut(char)) {
+        if self.need > 0 {
+            if (self.lower..=self.upper).contains(&byte) {
+                self.cp = (self.cp << 6) | (byte & 0x3f) as u32;
+                self.need -= 1;
+                self.lower = 0x80;
+                self.upper = 0xbf;
+                if self.need == 0 {
+                    out(char::from_u32(self.cp).unwrap_or(REPLACEMENT_CHARACTER));
+                }
+                return;
+            }
+            self.need = 0;
+            out(REPLACEMENT_CHARACTER);
+        }
+        (self.lower, self.upper) = (0x80, 0xbf);
+        match byte {
+            0x00..=0x7f => out(byte as char),
+            0xc2..=0xdf => self.start(byte & 0x1f, 1),
+            0xe0..=0xef => {
+                self.start(byte & 0x0f, 2);
+                match byte {
+                    0xe0 => self.lower = 0xa0, // Overlong
+                    0xed => self.upper = 0x9f, // Surrogates
+                    _ => {}
+                }
+            }
+            0xf0..=0xf4 => {
+                self.start(byte & 0x07, 3);
+                match byte {
+                    0xf0 => self.lower = 0x90, // Overlong
+                    0xf4 => self.upper = 0x8f, // Above U+10FFFF
+                    _ => {}
+                }
+            }
+            _ => out(REPLACEMENT_CHARACTER),
+        }
+    }
+
+    /// Ends the input, calling out with U+FFFD if a sequence was left open.
+    pub(crate) fn flush(&mut self, mut out: impl FnMut(char)) {
+        if self.need > 0 {
+            self.need = 0;
+            out(REPLACEMENT_CHARACTER);
+        }
+    }
+
+    fn start(&mut self, bits: u8, need: u8) {
+        self.cp = bits as u32;
+        self.need = need;
+    }
+}
+
+// Zero width: combining marks, zero width spaces and joiners, variation selectors.
+const ZERO_WIDTH: &[(u32, u32)] = &[
+    (0x0300, 0x036f),
+    (0x0483, 0x0489),
+    (0x0591, 0x05bd),
+    (0x0610, 0x061a),
+    (0x064b, 0x065f),
+    (0x0e31, 0x0e31),
+    (0x0e34, 0x0e3a),
+    (0x0e47, 0x0e4e),
+    (0x1ab0, 0x1aff),
+    (0x1dc0, 0x1dff),
+    (0x200b, 0x200f),
+    (0x2028, 0x202e),
+    (0x2060, 0x2064),
+    (0x20d0, 0x20ff),
+    (0xfe00, 0xfe0f),
+    (0xfe20, 0xfe2f),
+    (0xfeff, 0xfeff),
+    (0xe0100, 0xe01ef),
+];
+
+// East Asian Wide and Fullwidth, plus emoji presentation characters.
+const WIDE: &[(u32, u32)] = &[
+    (0x1100, 0x115f),
+    (0x231a, 0x231b),
+    (0x2329, 0x232a),
+    (0x23e9, 0x23ec),
+    (0x23f0, 0x23f0),
+    (0x23f3, 0x23f3),
+    (0x25fd, 0x25fe),
+    (0x2614, 0x2615),
+    (0x2648, 0x2653),
+    (0x267f, 0x267f),
+    (0x2693, 0x2693),
+    (0x26a1, 0x26a1),
+    (0x26aa, 0x26ab),
+    (0x26bd, 0x26be),
+    (0x26c4, 0x26c5),
+    (0x26ce, 0x26ce),
+    (0x26d4, 0x26d4),
+    (0x26ea, 0x26ea),
+    (0x26f2, 0x26f3),
+    (0x26f5, 0x26f5),
+    (0x26fa, 0x26fa),
+    (0x26fd, 0x26fd),
+    (0x2705, 0x2705),
+    (0x270a, 0x270b),
+    (0x2728, 0x2728),
+    (0x274c, 0x274c),
+    (0x274e, 0x274e),
+    (0x2753, 0x2755),
+    (0x2757, 0x2757),
+    (0x2795, 0x2797),
+    (0x27b0, 0x27b0),
+    (0x27bf, 0x27bf),
+    (0x2b1b, 0x2b1c),
+    (0x2b50, 0x2b50),
+    (0x2b55, 0x2b55),
+    (0x2e80, 0x303e),
+    (0x3041, 0x33ff),
+    (0x3400, 0x4dbf),
+    (0x4e00, 0x9fff),
+    (0xa000, 0xa4cf),
+    (0xa960, 0xa97f),
+    (0xac00, 0xd7a3),
+    (0xf900, 0xfaff),
+    (0xfe10, 0xfe19),
+    (0xfe30, 0xfe6f),
+    (0xff00, 0xff60),
+    (0xffe0, 0xffe6),
+    (0x16fe0, 0x16fe4),
+    (0x17000, 0x18aff),
+    (0x1b000, 0x1b2ff),
+    (0x1f004, 0x1f004),
+    (0x1f0cf, 0x1f0cf),
+    (0x1f18e, 0x1f18e),
+    (0x1f191, 0x1f19a),
+    (0x1f200, 0x1f251),
+    (0x1f300, 0x1f64f),
+    (0x1f680, 0x1f6ff),
+    (0x1f7e0, 0x1f7eb),
+    (0x1f90c, 0x1f9ff),
+    (0x1fa70, 0x1faff),
+    (0x20000, 0x2fffd),
+    (0x30000, 0x3fffd),
+];
+
+fn in_table(table: &[(u32, u32)], c: u32) -> bool {
+    table
+        .binary_search_by(|&(lo, hi)| {
+            if hi < c {
+                core::cmp::Ordering::Less
+            } else if lo > c {
+                core::cmp::Ordering::Greater
+            } else {
+                core::cmp::Ordering::Equal
+            }
+        })
+        .is_ok()
+}
+
+/// char_width
+///
+/// Gets the number of terminal cells c occupies: 0 for control characters
+/// and combining marks, 2 for East Asian wide and fullwidth characters and
+/// emoji, otherwise 1.
+pub fn char_width(c: char) -> usize {
+    let cp = c as u32;
+    if cp < 0x20 || (0x7f..0xa0).contains(&cp) || in_table(ZERO_WIDTH, cp) {
+        0
+    } else if cp < 0x1100 {
+        1
+    } else if in_table(WIDE, cp) {
+        2
+    } else {
+        1
+    }
+}
+
+fn is_continuation(byte: u8) -> bool {
+    byte & 0xc0 == 0x80
+}
+
+// Length of the sequence a lead byte starts, or 1 for bytes that cannot start one.
+fn sequence_len(lead: u8) -> usize {
+    match lead {
+        0xc2..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf4 => 4,
+        _ => 1,
+    }
+}
+
+impl HistoryBuffer {
+    /// align_utf8
+    ///
+    /// Clamps range to the live history and moves both ends to character
+    /// boundaries: the start forward past continuation bytes, and the end back
+    /// before a character that would be cut off.
+    pub fn align_utf8(&self, range: Range<usize>) -> Range<usize> {
+        let end = range.end.min(self.next_running);
+        let mut start = range.start.max(self.get_index()).min(end);
+        for _ in 0..3 {
+            match self.get(start) {
+                Some(b) if start < end && is_continuation(b) => start += 1,
+                _ => break,
+            }
+        }
+        let mut aligned_end = end;
+        for k in 1..=3.min(end - start) {
+            let b = self.get(end - k).unwrap_or(0);
+            if !is_continuation(b) {
+                if sequence_len(b) > k {
+                    aligned_end = end - k;
+                }
+                break;
+            }
+        }
+        start..aligned_end
+    }
+
+    /// get_str_lossy
+    ///
+    /// Returns the text in range, aligned to character boundaries, along
+    /// with the absolute range actually returned. Invalid bytes inside the
+    /// range become U+FFFD. The text is borrowed from the buffer when the
+    /// range does not wrap around the end of the ring and is valid UTF-8.
+    pub fn get_str_lossy(&self, range: Range<usize>) -> (Cow<'_, str>, Range<usize>) {
+        let range = self.align_utf8(range);
+        if let (first, []) = ring::slices(&self.buf, range.clone())
+            && let Ok(text) = core::str::from_utf8(first)
+        {
+            return (Cow::Borrowed(text), range);
+        }
+        let (v, _) = self.get_vec_and_index(range.start, range.len());
+        let text = match String::from_utf8(v) {
+            Ok(s) => s,
+            Err(e) => String::from_utf8_lossy(e.as_bytes()).into_owned(),
+        };
+        (Cow::Owned(text), range)
+    }
+
+    /// get_utf8
+    ///
+    /// Like `get_str_lossy`, but fails if the aligned range is not valid UTF-8.
+    pub fn get_utf8(
+        &self,
+        range: Range<usize>,
+    ) -> Result<(String, Range<usize>), core::str::Utf8Error> {
+        let range = self.align_utf8(range);
+        let (v, _) = self.get_vec_and_index(range.start, range.len());
+        match String::from_utf8(v) {
+            Ok(s) => Ok((s, range)),
+            Err(e) => Err(e.utf8_error()),
+        }
+    }
+
+    /// chars_rev
+    ///
+    /// Iterates the characters of the history from the newest byte back to
+    /// the oldest. Invalid bytes, including a character whose head has been
+    /// evicted, come back as U+FFFD.
+    pub fn chars_rev(&self) -> CharsRev<'_> {
+        CharsRev {
+            hb: self,
+            pos: self.next_running,
+        }
+    }
+}
+
+/// Iterator returned by `HistoryBuffer::chars_rev`.
+#[derive(Debug, Clone)]
+pub struct CharsRev<'a> {
+    hb: &'a HistoryBuffer,
+    pos: usize,
+}
+
+impl CharsRev<'_> {
+    /// index
+    ///
+    /// Gets the absolute index of the last character returned.
+    pub fn index(&self) -> usize {
+        self.pos
+    }
+}
+
+impl Iterator for CharsRev<'_> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        let oldest = self.hb.get_index();
+        if self.pos <= oldest {
+            return None;
+        }
+        let mut start = self.pos - 1;
+        while start > oldest && self.pos - start < 4 && is_continuation(self.hb.get(start)?) {
+            start -= 1;
+        }
+        let (bytes, _) = self.hb.get_vec_and_index(start, self.pos - start);
+        match core::str::from_utf8(&bytes) {
+            Ok(s) if s.chars().count() == 1 => {
+                self.pos = start;
+                s.chars().next()
+            }
+            _ => {
+                self.pos -= 1;
+                Some(REPLACEMENT_CHARACTER)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn decode(input: &[u8]) -> String {
+        let mut decoder = Utf8Decoder::new();
+        let mut s = String::new();
+        for &b in input {
+            decoder.feed(b, |c| s.push(c));
+        }
+        s
+    }
+
+    #[test]
+    fn test_decoder() {
+        for input in [
+            "héllo €𝄞".as_bytes(),
+            b"a\xe2\x82b",
+            b"\xc0\xafx",
+            b"\xed\xa0\x80",
+            b"\xf0\x9f\x98!",
+        ] {
+            println!("        {:?}:", input);
+            assert_eq!(decode(input), String::from_utf8_lossy(input));
+        }
+    }
+
+    #[test]
+    fn test_char_width() {
+        assert_eq!(char_width('a'), 1);
+        assert_eq!(char_width('\t'), 0);
+        assert_eq!(char_width('\u{301}'), 0);
+        assert_eq!(char_width('é'), 1);
+        assert_eq!(char_width('中'), 2);
+        assert_eq!(char_width('Ｆ'), 2);
+        assert_eq!(char_width('🦀'), 2);
+    }
+
+    #[test]
+    fn test_utf8_retrieval() {
+        let mut hb = HistoryBuffer::new(8);
+        hb.add("x€é".as_bytes());
+        hb.add("ab".as_bytes());
+        hb.add("é".as_bytes());
+
+        println!("        head of the euro sign evicted:");
+        assert_eq!(hb.get_index(), 2);
+        assert_eq!(hb.get_vec(0, 100), b"\x82\xac\xc3\xa9ab\xc3\xa9");
+        assert_eq!(hb.get_str_lossy(0..100), (Cow::from("éabé"), 4..10));
+        assert_eq!(hb.get_utf8(5..9), Ok(("ab".to_string(), 6..8)));
+        assert_eq!(hb.get_str_lossy(9..10).0, "");
+
+        println!("        borrowed unless wrapped or invalid:");
+        assert!(matches!(hb.get_str_lossy(6..8).0, Cow::Borrowed("ab")));
+        assert!(matches!(hb.get_str_lossy(4..10).0, Cow::Owned(_)));
+        let mut invalid = HistoryBuffer::new(8);
+        invalid.add(b"a\xffb");
+        assert_eq!(invalid.get_str_lossy(0..3).0, "a\u{fffd}b");
+        assert!(matches!(invalid.get_str_lossy(0..3).0, Cow::Owned(_)));
+
+        println!("        chars_rev:");
+        let mut chars = hb.chars_rev();
+        assert_eq!(chars.next(), Some('é'));
+        assert_eq!(chars.index(), 8);
+        assert_eq!(chars.collect::<String>(), "baé\u{fffd}\u{fffd}");
+
+        hb.add(b"\xffz");
+        assert!(hb.get_utf8(6..12).is_err());
+    }
+}
@@ -0,0 +1,241 @@
+//! Viewport
+//!
+//! A scrollback window over a [`HistoryBuffer`]. While scrolled up, the
+//! viewport is anchored to the absolute index of its top line, so it keeps
+//! showing the same content as `add` appends more output. At the bottom it
+//! follows new output. When the anchored line is evicted, the viewport
+//! clamps to the oldest line and `sync` reports it.
+//!
+//! ```rust
+//! use historybuffer::{HistoryBuffer, Viewport};
+//!
+//! let mut hb = HistoryBuffer::new(64);
+//! let mut view = Viewport::new(2);
+//! hb.add(b"one\ntwo\nthree\n");
+//! view.scroll_up(&hb, 1);
+//! hb.add(b"four\n");
+//!
+//! let lines: Vec<String> = view.visible_lines(&hb).into_iter().map(|l| l.text).collect();
+//! assert_eq!(lines, vec!["one", "two"]);
+//! ```
+
+use alloc::vec::Vec;
+
+use crate::cooked::line_start;
+use crate::{CookedLine, Evicted, HistoryBuffer};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Viewport {
+    anchor: Option<usize>, // Start of the top line, None while following the bottom
+    height: usize,
+}
+
+impl Viewport {
+    pub fn new(height: usize) -> Self {
+        Self {
+            anchor: None,
+            height: height.max(1),
+        }
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// set_height
+    ///
+    /// Changes the number of visible lines.
+    pub fn set_height(&mut self, height: usize) {
+        self.height = height.max(1);
+    }
+
+    /// anchor
+    ///
+    /// Gets the absolute index of the top line, or None while following new output.
+    pub fn anchor(&self) -> Option<usize> {
+        self.anchor
+    }
+
+    /// is_at_bottom
+    ///
+    /// True while the viewport is following new output.
+    pub fn is_at_bottom(&self) -> bool {
+        self.anchor.is_none()
+    }
+
+    /// sync
+    ///
+    /// Checks the anchor against the history. If the anchored line has been
+    /// evicted, the viewport is moved to the oldest line and Evicted is returned.
+    pub fn sync(&mut self, hb: &HistoryBuffer) -> Result<(), Evicted> {
+        match self.anchor {
+            Some(anchor) if anchor < hb.get_index() => {
+                self.anchor = Some(hb.get_index());
+                self.settle(hb);
+                Err(Evicted)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// get_top
+    ///
+    /// Gets the absolute index of the first visible line.
+    pub fn get_top(&self, hb: &HistoryBuffer) -> usize {
+        match self.anchor {
+            Some(anchor) => anchor.max(hb.get_index()),
+            None => bottom_top(hb, self.height),
+        }
+    }
+
+    /// scroll_up by lines
+    pub fn scroll_up(&mut self, hb: &HistoryBuffer, lines: usize) {
+        let mut top = self.get_top(hb);
+        for _ in 0..lines {
+            match prev_line_start(hb, top) {
+                Some(prev) => top = prev,
+                None => break,
+            }
+        }
+        self.anchor = Some(top);
+        self.settle(hb);
+    }
+
+    /// scroll_down by lines, following new output once the bottom is reached
+    pub fn scroll_down(&mut self, hb: &HistoryBuffer, lines: usize) {
+        let mut top = self.get_top(hb);
+        for _ in 0..lines {
+            match next_line_start(hb, top) {
+                Some(next) => top = next,
+                None => break,
+            }
+        }
+        self.anchor = Some(top);
+        self.settle(hb);
+    }
+
+    /// page_up by one viewport height
+    pub fn page_up(&mut self, hb: &HistoryBuffer) {
+        self.scroll_up(hb, self.height);
+    }
+
+    /// page_down by one viewport height
+    pub fn page_down(&mut self, hb: &HistoryBuffer) {
+        self.scroll_down(hb, self.height);
+    }
+
+    /// scroll_to_top
+    ///
+    /// Anchors the viewport to the oldest line in the history.
+    pub fn scroll_to_top(&mut self, hb: &HistoryBuffer) {
+        self.anchor = Some(hb.get_index());
+        self.settle(hb);
+    }
+
+    /// scroll_to_bottom
+    ///
+    /// Follows new output.
+    pub fn scroll_to_bottom(&mut self) {
+        self.anchor = None;
+    }
+
+    /// visible_lines
+    ///
+    /// Returns up to height lines starting at the top line, as cooked lines.
+    pub fn visible_lines(&self, hb: &HistoryBuffer) -> Vec<CookedLine> {
+        let top = self.get_top(hb);
+        let mut end = top;
+        for _ in 0..self.height {
+            match next_line_start(hb, end) {
+                Some(next) => end = next,
+                None => {
+                    end = hb.next_running;
+                    break;
+                }
+            }
+        }
+        let mut lines = hb.get_cooked_lines(top, end - top);
+        lines.retain(|line| line.range.start >= top);
+        lines.truncate(self.height);
+        lines
+    }
+
+    // Anchoring at or past the bottom page means following the bottom again.
+    fn settle(&mut self, hb: &HistoryBuffer) {
+        if let Some(anchor) = self.anchor
+            && anchor >= bottom_top(hb, self.height)
+        {
+            self.anchor = None;
+        }
+    }
+}
+
+fn prev_line_start(hb: &HistoryBuffer, start: usize) -> Option<usize> {
+    (start > hb.get_index()).then(|| line_start(hb, start - 1))
+}
+
+fn next_line_start(hb: &HistoryBuffer, start: usize) -> Option<usize> {
+    (start.max(hb.get_index())..hb.next_running)
+        .find(|&i| hb.get(i) == Some(b'\n'))
+        .map(|i| i + 1)
+        .filter(|&next| next < hb.next_running)
+}
+
+// Top line of the last full page.
+fn bottom_top(hb: &HistoryBuffer, height: usize) -> usize {
+    let mut top = line_start(hb, hb.next_running.saturating_sub(1));
+    for _ in 1..height {
+        match prev_line_start(hb, top) {
+            Some(prev) => top = prev,
+            None => break,
+        }
+    }
+    top
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn texts(view: &Viewport, hb: &HistoryBuffer) -> Vec<String> {
+        view.visible_lines(hb).into_iter().map(|l| l.text).collect()
+    }
+
+    #[test]
+    fn test_viewport() {
+        let mut hb = HistoryBuffer::new(32);
+        let mut view = Viewport::new(2);
+        hb.add(b"l1\nl2\nl3\nl4\nl5");
+
+        println!("        following:");
+        assert_eq!(texts(&view, &hb), vec!["l4", "l5"]);
+        hb.add(b"\nl6\n");
+        assert_eq!(texts(&view, &hb), vec!["l5", "l6"]);
+
+        println!("        anchored:");
+        view.page_up(&hb);
+        assert_eq!(view.anchor(), Some(6));
+        hb.add(b"l7\n");
+        assert_eq!(texts(&view, &hb), vec!["l3", "l4"]);
+        view.scroll_up(&hb, 5);
+        assert_eq!(texts(&view, &hb), vec!["l1", "l2"]);
+        view.scroll_down(&hb, 1);
+        assert_eq!(texts(&view, &hb), vec!["l2", "l3"]);
+
+        println!("        evicted:");
+        hb.add(b"l8\nl9\nlA\nlB\nlC\n");
+        assert_eq!(view.sync(&hb), Err(Evicted));
+        assert_eq!(view.anchor(), Some(hb.get_index()));
+        assert_eq!(texts(&view, &hb), vec!["2", "l3"]);
+        assert_eq!(view.sync(&hb), Ok(()));
+
+        view.page_down(&hb);
+        assert_eq!(texts(&view, &hb), vec!["l4", "l5"]);
+        view.scroll_down(&hb, 100);
+        assert!(view.is_at_bottom());
+        assert_eq!(texts(&view, &hb), vec!["lB", "lC"]);
+        view.scroll_to_top(&hb);
+        assert_eq!(texts(&view, &hb), vec!["2", "l3"]);
+    }
+}
@@ -0,0 +1,374 @@
+//! WrapView
+//!
+//! Soft-wraps history to a terminal width for scrollback rendering. Logical
+//! lines end at `\n`; each one takes as many display rows as its characters
+//! need, using `char_width` so wide CJK characters and emoji count as two
+//! cells and are never split across rows. Escape sequences take no space.
+//!
+//! The view keeps the row count of every line, so finding "rows N..M from the
+//! bottom" only decodes the lines that are actually shown; the newer lines
+//! are skipped by their counts. Counts are redone when the width changes, and
+//! for the oldest line when its head is evicted. A character left unfinished
+//! by a control byte counts as one U+FFFD, so no bytes carry over a `\n`.
+
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::HistoryBuffer;
+use crate::ansi::{Event, Parser};
+use crate::utf8::{Utf8Decoder, char_width};
+
+pub(crate) const TAB_WIDTH: usize = 8;
+pub(crate) const TAB: u8 = u8::MAX; // Width marker for a tab, which depends on its column
+
+#[derive(Debug, Clone)]
+struct Line {
+    start: usize,
+    end: usize,
+    widths: Vec<u8>,
+    rows: usize,
+    complete: bool,
+}
+
+/// One display row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Row {
+    /// Raw bytes shown on this row.
+    pub range: Range<usize>,
+    /// Row text, with escape sequences removed and tabs expanded.
+    pub text: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct WrapView {
+    width: usize,
+    parser: Parser,
+    decoder: Utf8Decoder,
+    scanned: usize,
+    line_start: usize,
+    lines: VecDeque<Line>,
+    total_rows: usize,
+}
+
+impl WrapView {
+    pub fn new(width: usize) -> Self {
+        Self {
+            width: width.max(1),
+            parser: Parser::new(),
+            decoder: Utf8Decoder::new(),
+            scanned: 0,
+            line_start: 0,
+            lines: VecDeque::new(),
+            total_rows: 0,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// set_width
+    ///
+    /// Changes the wrap width and recounts the rows of every line.
+    pub fn set_width(&mut self, width: usize) {
+        self.width = width.max(1);
+        self.total_rows = 0;
+        for line in self.lines.iter_mut() {
+            line.rows = wrap_breaks(&line.widths, self.width).len();
+            self.total_rows += line.rows;
+        }
+    }
+
+    /// total_rows
+    ///
+    /// Gets the number of display rows all lines take at the current width.
+    pub fn total_rows(&self) -> usize {
+        self.total_rows
+    }
+
+    /// update
+    ///
+    /// Takes in bytes added since the last update and forgets lines that have
+    /// been evicted.
+    pub fn update(&mut self, hb: &HistoryBuffer) {
+        if self.scanned < hb.get_index() {
+            // Everything seen so far is gone, including an unfinished line
+            self.parser = Parser::new();
+            self.decoder = Utf8Decoder::new();
+            self.scanned = hb.get_index();
+            self.line_start = self.scanned;
+            self.lines.clear();
+            self.total_rows = 0;
+        }
+        let (data, inn) = hb.get_vec_and_index(self.scanned, hb.next_running - self.scanned);
+        if let Some(line) = self.lines.back()
+            && !line.complete
+        {
+            self.total_rows -= line.rows;
+        }
+        let width = self.width;
+        let mut decoder = self.decoder;
+        for (i, &byte) in data.iter().enumerate() {
+            let index = inn + i;
+            let Some((_, event)) = self.parser.feed(index, byte) else {
+                continue;
+            };
+            let line = self.open_line();
+            if let Event::Control(_) = event {
+                decoder.flush(|c| line.widths.push(char_width(c) as u8));
+            }
+            match event {
+                Event::Print(b) => decoder.feed(b, |c| line.widths.push(char_width(c) as u8)),
+                Event::Control(b'\t') => line.widths.push(TAB),
+                Event::Control(b'\n') => {
+                    line.end = index + 1;
+                    line.complete = true;
+                    line.rows = wrap_breaks(&line.widths, width).len();
+                    let rows = line.rows;
+                    self.total_rows += rows;
+                    self.line_start = index + 1;
+                }
+                _ => {}
+            }
+        }
+        self.decoder = decoder;
+        self.scanned = hb.next_running;
+        if self.line_start < self.scanned {
+            let line = self.open_line();
+            line.end = hb.next_running;
+            line.rows = wrap_breaks(&line.widths, width).len();
+            let rows = line.rows;
+            self.total_rows += rows;
+        }
+        self.evict(hb);
+    }
+
+    /// rows_from_bottom
+    ///
+    /// Returns the display rows in range, counting the newest row as 0, in
+    /// top to bottom order. Row text is read back from hb, which must be the
+    /// history this view was updated from.
+    pub fn rows_from_bottom(&self, hb: &HistoryBuffer, range: Range<usize>) -> Vec<Row> {
+        let mut rows = Vec::new();
+        let mut bottom = 0; // Bottom row number of the current line
+        for line in self.lines.iter().rev() {
+            if bottom >= range.end {
+                break;
+            }
+            let top = bottom + line.rows;
+            if top > range.start {
+                let line_rows = line_rows(hb, line, self.width);
+                for (i, row) in line_rows.into_iter().enumerate().rev() {
+                    let n = line.rows.checked_sub(i + 1).map(|up| bottom + up);
+                    if n.is_some_and(|n| range.contains(&n)) {
+                        rows.push(row);
+                    }
+                }
+            }
+            bottom = top;
+        }
+        rows.reverse();
+        rows
+    }
+
+    fn open_line(&mut self) -> &mut Line {
+        if self.lines.back().is_none_or(|line| line.complete) {
+            self.lines.push_back(Line {
+                start: self.line_start,
+                end: self.line_start,
+                widths: Vec::new(),
+                rows: 0,
+                complete: false,
+            });
+        }
+        self.lines.back_mut().unwrap()
+    }
+
+    fn evict(&mut self, hb: &HistoryBuffer) {
+        let oldest = hb.get_index();
+        while let Some(line) = self.lines.front() {
+            if line.end > oldest {
+                break;
+            }
+            self.total_rows -= line.rows;
+            self.lines.pop_front();
+        }
+        if let Some(line) = self.lines.front_mut()
+            && line.start < oldest
+        {
+            // The head of the oldest line is gone; measure what is left
+            line.start = oldest;
+            line.widths = line_chars(hb, line.start, line.end)
+                .iter()
+                .map(|&(_, c)| if c == '\t' { TAB } else { char_width(c) as u8 })
+                .collect();
+            self.total_rows -= line.rows;
+            line.rows = wrap_breaks(&line.widths, self.width).len();
+            self.total_rows += line.rows;
+        }
+    }
+}
+
+// Index of the first character of each row.
+pub(crate) fn wrap_breaks(widths: &[u8], width: usize) -> Vec<usize> {
+    let mut breaks = vec![0];
+    let mut col = 0;
+    for (i, &w) in widths.iter().enumerate() {
+        let w = if w == TAB {
+            TAB_WIDTH - col % TAB_WIDTH
+        } else {
+            w as usize
+        };
+        if col + w > width && col > 0 {
+            breaks.push(i);
+            col = 0;
+        }
+        col += w.min(width);
+    }
+    breaks
+}
+
+// Characters of a line with the raw index each one starts at.
+pub(crate) fn line_chars(hb: &HistoryBuffer, start: usize, end: usize) -> Vec<(usize, char)> {
+    let (data, inn) = hb.get_vec_and_index(start, end - start);
+    let mut parser = Parser::new();
+    let mut decoder = Utf8Decoder::new();
+    let mut chars = Vec::new();
+    let mut char_start = None;
+    for (i, &byte) in data.iter().enumerate() {
+        let index = inn + i;
+        match parser.feed(index, byte) {
+            Some((_, Event::Print(b))) => {
+                let first = *char_start.get_or_insert(index);
+                let mut emitted = false;
+                decoder.feed(b, |c| {
+                    chars.push((if emitted { index } else { first }, c));
+                    emitted = true;
+                });
+                if emitted {
+                    char_start = None;
+                }
+            }
+            Some((_, Event::Control(byte))) => {
+                decoder.flush(|c| chars.push((char_start.take().unwrap_or(index), c)));
+                if byte == b'\t' {
+                    chars.push((index, '\t'));
+                }
+            }
+            _ => {}
+        }
+    }
+    chars
+}
+
+fn line_rows(hb: &HistoryBuffer, line: &Line, width: usize) -> Vec<Row> {
+    let chars = line_chars(hb, line.start, line.end);
+    let widths: Vec<u8> = chars
+        .iter()
+        .map(|&(_, c)| if c == '\t' { TAB } else { char_width(c) as u8 })
+        .collect();
+    let breaks = wrap_breaks(&widths, width);
+    let mut rows = Vec::with_capacity(breaks.len());
+    for (n, &first) in breaks.iter().enumerate() {
+        let last = breaks.get(n + 1).copied().unwrap_or(chars.len());
+        let start = if n == 0 { line.start } else { chars[first].0 };
+        let end = chars.get(last).map_or(line.end, |&(index, _)| index);
+        let mut text = String::new();
+        for &(_, c) in &chars[first..last] {
+            if c == '\t' {
+                let spaces = TAB_WIDTH - text.chars().map(char_width).sum::<usize>() % TAB_WIDTH;
+                text.extend(core::iter::repeat_n(' ', spaces));
+            } else {
+                text.push(c);
+            }
+        }
+        rows.push(Row {
+            range: start..end,
+            text,
+        });
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn texts(rows: Vec<Row>) -> Vec<String> {
+        rows.into_iter().map(|row| row.text).collect()
+    }
+
+    #[test]
+    fn test_wrap_view() {
+        let mut hb = HistoryBuffer::new(64);
+        let mut view = WrapView::new(4);
+        hb.add(b"abcdefghij\n\x1b[31mxy\x1b[0m\n");
+        view.update(&hb);
+        hb.add("中文字".as_bytes());
+        view.update(&hb);
+
+        assert_eq!(view.total_rows(), 6);
+        assert_eq!(
+            texts(view.rows_from_bottom(&hb, 0..6)),
+            vec!["abcd", "efgh", "ij", "xy", "中文", "字"]
+        );
+        let rows = view.rows_from_bottom(&hb, 3..5);
+        assert_eq!(texts(rows.clone()), vec!["efgh", "ij"]);
+        assert_eq!(rows[1].range, 8..11);
+
+        println!("        width change:");
+        view.set_width(3);
+        assert_eq!(view.total_rows(), 8);
+        assert_eq!(
+            texts(view.rows_from_bottom(&hb, 0..3)),
+            vec!["中", "文", "字"]
+        );
+
+        println!("        eviction:");
+        hb.add(b"\n012345678901234567890123456789012345678901");
+        view.update(&hb);
+        assert_eq!(hb.get_index(), 11);
+        assert_eq!(view.total_rows(), 1 + 3 + 14);
+        assert_eq!(texts(view.rows_from_bottom(&hb, 17..18)), vec!["xy"]);
+
+        println!("        cleared inside a line:");
+        let mut hb = HistoryBuffer::new(64);
+        let mut view = WrapView::new(4);
+        hb.add(b"$ ");
+        view.update(&hb);
+        hb.add(b"ls\n");
+        hb.clear();
+        view.update(&hb);
+        assert_eq!(view.total_rows(), 0);
+        hb.add(b"ok");
+        view.update(&hb);
+        assert_eq!(view.total_rows(), 1);
+        assert_eq!(texts(view.rows_from_bottom(&hb, 0..10)), vec!["ok"]);
+    }
+
+    #[test]
+    fn test_wrap_view_truncated_char() {
+        let mut hb = HistoryBuffer::new(64);
+        let mut view = WrapView::new(1);
+        hb.add(b"\xe4\n\xb8\xad");
+        view.update(&hb);
+        assert_eq!(view.total_rows(), 3);
+        assert_eq!(
+            texts(view.rows_from_bottom(&hb, 0..10)),
+            vec!["\u{fffd}", "\u{fffd}", "\u{fffd}"]
+        );
+
+        hb.clear();
+        hb.add(b"\xe4\na");
+        view.update(&hb);
+        assert_eq!(view.total_rows(), 2);
+        assert_eq!(
+            texts(view.rows_from_bottom(&hb, 0..10)),
+            vec!["\u{fffd}", "a"]
+        );
+    }
+}